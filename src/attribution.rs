@@ -0,0 +1,247 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use semver::Version;
+
+use crate::feature::{Feature, Span};
+use crate::powerset::FeatureSetAnalysis;
+
+/// What enabling a single cargo feature adds on top of the analysis with no cargo features.
+#[derive(Debug)]
+pub struct FeatureAttribution<'a> {
+    pub cargo_feature: &'a str,
+    pub baseline_version: Option<&'a Version>,
+    pub minimum_version: Option<&'a Version>,
+    /// Rust features with uses that are not present in the baseline, along with those uses.
+    pub new_uses: Vec<(&'a Feature, Vec<&'a Span>)>,
+    /// Crates that are only compiled when the cargo feature is enabled.
+    pub new_crates: Vec<&'a str>,
+}
+
+impl<'a> FeatureAttribution<'a> {
+    /// Returns the new feature uses that require a newer compiler than the baseline.
+    pub fn raising_uses(&self) -> impl Iterator<Item = &(&'a Feature, Vec<&'a Span>)> {
+        let baseline_version = self.baseline_version;
+        self.new_uses.iter().filter(move |(feature, _)| feature.since.as_ref() > baseline_version)
+    }
+}
+
+/// Attributes the differences between each single-feature analysis and the analysis with no cargo features.
+///
+/// Combinations of several features are ignored, as their differences cannot be attributed to any of them.
+pub fn attribute(analyses: &[FeatureSetAnalysis]) -> Result<Vec<FeatureAttribution<'_>>> {
+    let baseline = match analyses.iter().find(|a| a.cargo_features.is_empty()) {
+        Some(baseline) => &baseline.analysis,
+        None => bail!("the analysis with no cargo features is missing"),
+    };
+    let baseline_crates = baseline.crate_names();
+
+    let attributions = analyses
+        .iter()
+        .filter(|a| a.cargo_features.len() == 1)
+        .map(|a| {
+            let new_uses = a
+                .analysis
+                .all_features()
+                .into_iter()
+                .filter_map(|feature| {
+                    let baseline_uses =
+                        baseline.all_feature_uses(&feature.name).into_iter().map(use_key).collect::<Vec<_>>();
+                    let uses = a
+                        .analysis
+                        .all_feature_uses(&feature.name)
+                        .into_iter()
+                        .filter(|span| !baseline_uses.contains(&use_key(span)))
+                        .collect::<Vec<_>>();
+                    if uses.is_empty() { None } else { Some((feature, uses)) }
+                })
+                .collect();
+            let new_crates =
+                a.analysis.crate_names().into_iter().filter(|name| !baseline_crates.contains(name)).collect();
+
+            FeatureAttribution {
+                cargo_feature: &a.cargo_features[0],
+                baseline_version: baseline.minimum_version(),
+                minimum_version: a.analysis.minimum_version(),
+                new_uses,
+                new_crates,
+            }
+        })
+        .collect();
+    Ok(attributions)
+}
+
+// Build script outputs are saved in the target directory, which is different for each set of cargo features,
+// under a directory named after the package and a hash that depends on its features too. So their uses are
+// compared by package, e.g. `target/minver/no-features/debug/build/foo-0123456789abcdef/output` as `build/foo/output`.
+fn use_key(span: &Span) -> (String, usize, usize, usize, usize) {
+    let file_name = match build_script_package(Path::new(&span.file_name)) {
+        Some(package) => format!("build/{}/output", package),
+        None => span.file_name.clone(),
+    };
+    (file_name, span.start_line, span.start_col, span.end_line, span.end_col)
+}
+
+fn build_script_package(path: &Path) -> Option<&str> {
+    if !path.ends_with("output") {
+        return None;
+    }
+    let out_dir = path.parent()?;
+    if !out_dir.parent()?.ends_with("build") {
+        return None;
+    }
+    let name = out_dir.file_name()?.to_str()?;
+    name.rfind('-').map(|pos| &name[..pos])
+}
+
+/// Renders the attributions as a markdown table.
+pub fn markdown_table(attributions: &[FeatureAttribution<'_>]) -> String {
+    fn version(version: Option<&Version>) -> String {
+        version.map_or_else(|| "-".into(), ToString::to_string)
+    }
+
+    let mut table = String::new();
+    table.push_str("| Cargo feature | Minimum version | Raised by | New dependencies |\n");
+    table.push_str("|---|---|---|---|\n");
+    for attribution in attributions {
+        let raised_by = attribution
+            .raising_uses()
+            .map(|(feature, uses)| {
+                let mut files = uses.iter().map(|span| span.file_name.as_str()).collect::<Vec<_>>();
+                files.dedup();
+                format!("`{}` ({}) in {}", feature.name, version(feature.since.as_ref()), files.join(", "))
+            })
+            .collect::<Vec<_>>();
+        let new_crates = attribution.new_crates.iter().map(|name| format!("`{}`", name)).collect::<Vec<_>>();
+
+        let minimum_version = if attribution.minimum_version > attribution.baseline_version {
+            format!("{} → {}", version(attribution.baseline_version), version(attribution.minimum_version))
+        } else {
+            version(attribution.minimum_version)
+        };
+
+        // Writing to a String cannot fail.
+        let _ = writeln!(
+            table,
+            "| `{}` | {} | {} | {} |",
+            attribution.cargo_feature,
+            minimum_version,
+            if raised_by.is_empty() { "-".into() } else { raised_by.join("<br>") },
+            if new_crates.is_empty() { "-".into() } else { new_crates.join(", ") },
+        );
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::feature::{Analysis, CargoFileAnalysis, CrateAnalysis, FeatureKind};
+    use crate::powerset::feature_set_id;
+
+    fn feature(name: &str, kind: FeatureKind, since: &str) -> Feature {
+        Feature { name: name.into(), kind, since: since.parse().ok() }
+    }
+
+    fn span(file_name: &str, line: usize) -> Span {
+        Span { file_name: file_name.into(), start_line: line, start_col: 0, end_line: line, end_col: 10 }
+    }
+
+    fn crate_analysis(name: &str, features: &[(&str, &str, usize)]) -> CrateAnalysis {
+        let file_name = format!("{}/src/lib.rs", name);
+        let mut uses = HashMap::new();
+        for (feature, _, line) in features {
+            uses.insert(feature.to_string(), vec![span(&file_name, *line)]);
+        }
+        let features = features.iter().map(|(name, since, _)| feature(name, FeatureKind::Lang, since)).collect();
+        CrateAnalysis { name: name.into(), features, uses, ..Default::default() }
+    }
+
+    // Each set of cargo features has its own target directory, and build directories are named after hashes.
+    fn build_output(feature_set: &str, hash: &str) -> CargoFileAnalysis {
+        let path = format!("target/minver/{}/debug/build/foo-{}/output", feature_set, hash);
+        let mut output = CargoFileAnalysis { path: path.into(), ..Default::default() };
+        output.add_line_use(feature("build_script_rustc_env", FeatureKind::Cargo, "1.19.0"), 1, 10);
+        output
+    }
+
+    fn feature_set(cargo_features: &[&str], crates: Vec<CrateAnalysis>, hash: &str) -> FeatureSetAnalysis {
+        let cargo_features = cargo_features.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        let mut analysis = Analysis::from(crates);
+        analysis.add_cargo_file(build_output(&feature_set_id(&cargo_features), hash));
+        FeatureSetAnalysis { cargo_features, analysis }
+    }
+
+    fn analyses() -> Vec<FeatureSetAnalysis> {
+        vec![
+            feature_set(&[], vec![crate_analysis("foo", &[("i128_type", "1.26.0", 1)])], "0000000000000000"),
+            feature_set(
+                &["std"],
+                vec![crate_analysis("foo", &[("i128_type", "1.26.0", 1), ("async_await", "1.39.0", 5)])],
+                "1111111111111111",
+            ),
+            feature_set(
+                &["serde"],
+                vec![
+                    crate_analysis("foo", &[("i128_type", "1.26.0", 1)]),
+                    crate_analysis("serde", &[("i128_type", "1.26.0", 2)]),
+                ],
+                "2222222222222222",
+            ),
+        ]
+    }
+
+    #[test]
+    fn raising_uses() {
+        let analyses = analyses();
+        let attributions = attribute(&analyses).unwrap();
+        assert_eq!(vec!["std", "serde"], attributions.iter().map(|a| a.cargo_feature).collect::<Vec<_>>());
+
+        // The build script output is the same in every set, even if it's saved somewhere else.
+        let std = &attributions[0];
+        let new_uses =
+            std.new_uses.iter().map(|(feature, uses)| (feature.name.as_str(), uses.len())).collect::<Vec<_>>();
+        assert_eq!(vec![("async_await", 1)], new_uses);
+        let raising = std.raising_uses().map(|(feature, _)| feature.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["async_await"], raising);
+        assert!(std.new_crates.is_empty());
+
+        // New uses of features the baseline already needs don't raise the minimum version.
+        let serde = &attributions[1];
+        let new_uses =
+            serde.new_uses.iter().map(|(feature, uses)| (feature.name.as_str(), uses.len())).collect::<Vec<_>>();
+        assert_eq!(vec![("i128_type", 1)], new_uses);
+        assert_eq!(0, serde.raising_uses().count());
+        assert_eq!(vec!["serde"], serde.new_crates);
+    }
+
+    #[test]
+    fn missing_baseline() {
+        let analyses = analyses().into_iter().skip(1).collect::<Vec<_>>();
+        assert!(attribute(&analyses).is_err());
+    }
+
+    #[test]
+    fn table() {
+        let analyses = analyses();
+        let attributions = attribute(&analyses).unwrap();
+        let expected = "\
+| Cargo feature | Minimum version | Raised by | New dependencies |
+|---|---|---|---|
+| `std` | 1.26.0 → 1.39.0 | `async_await` (1.39.0) in foo/src/lib.rs | - |
+| `serde` | 1.26.0 | - | `serde` |
+";
+        assert_eq!(expected, markdown_table(&attributions));
+    }
+
+    #[test]
+    fn build_script_packages() {
+        let path = Path::new("target/minver/features-0123456789abcdef/debug/build/foo-bar-0123456789abcdef/output");
+        assert_eq!(Some("foo-bar"), build_script_package(path));
+        assert_eq!(None, build_script_package(Path::new("target/debug/build/foo-0123456789abcdef/stderr")));
+        assert_eq!(None, build_script_package(Path::new("src/output")));
+    }
+}
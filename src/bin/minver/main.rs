@@ -1,20 +1,127 @@
 use anyhow::Result;
 use structopt::StructOpt;
 
-use cargo_minver::{Driver, Options};
+use cargo_minver::{attribute, describe_feature_set, markdown_table, worst_case, CoverageReport, Driver, Options};
 
 #[derive(Debug, StructOpt)]
 #[structopt(bin_name = "cargo")]
 enum Cargo {
-    Minver(Options),
+    Minver(Minver),
+}
+
+#[derive(Debug, StructOpt)]
+struct Minver {
+    #[structopt(flatten)]
+    options: Options,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// List the lang features accepted by the pinned toolchain, and whether they are detected.
+    Coverage {
+        /// Print the report as JSON.
+        #[structopt(long)]
+        json: bool,
+    },
 }
 
 fn main() -> Result<()> {
-    let Cargo::Minver(options) = Cargo::from_args();
+    let Cargo::Minver(Minver { options, command }) = Cargo::from_args();
+
+    let mut driver = Driver::from(options);
+    if let Some(Command::Coverage { json }) = command {
+        let report = driver.coverage()?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            print_coverage(&report);
+        }
+        return Ok(());
+    }
+
+    if driver.checks_feature_sets() {
+        let analyses = driver.execute_feature_sets()?;
+        for feature_set in &analyses {
+            let version = feature_set.analysis.minimum_version().map_or_else(|| "unknown".into(), ToString::to_string);
+            println!("{}: {}", describe_feature_set(&feature_set.cargo_features), version);
+        }
+        if let Some(worst) = worst_case(&analyses) {
+            if let Some(version) = worst.analysis.minimum_version() {
+                println!("worst case: {} ({})", version, describe_feature_set(&worst.cargo_features));
+            }
+        }
+
+        let attributions = attribute(&analyses)?;
+        if !attributions.is_empty() {
+            println!();
+            print!("{}", markdown_table(&attributions));
+        }
+        return Ok(());
+    }
 
-    let analysis = Driver::from(options).execute()?;
+    let analysis = driver.execute()?;
     let features = analysis.all_features();
     dbg!(&features);
 
+    for feature in &features {
+        for target_use in analysis.all_feature_uses_by_target(&feature.name) {
+            println!("{}: {}", feature.name, target_use);
+        }
+    }
+
+    for target in analysis.targets() {
+        if let Some(version) = analysis.target_minimum_version(target) {
+            println!("{}: {}", target, version);
+        }
+    }
+
+    for feature in analysis.inactive_features() {
+        for inactive_use in analysis.inactive_feature_uses(&feature.name) {
+            println!("{}: {}", feature.name, inactive_use);
+        }
+    }
+
+    for feature in analysis.conditional_features() {
+        for conditional_use in analysis.conditional_feature_uses(&feature.name) {
+            println!("{}: {}", feature.name, conditional_use);
+        }
+    }
+
+    for (feature, conditional_use) in analysis.gate_violations() {
+        if let Some(since) = &feature.since {
+            eprintln!(
+                "warning: `{}` needs {}, but {} allows {} at {}",
+                feature.name, since, conditional_use.gate.condition, conditional_use.gate.version, conditional_use.span
+            );
+        }
+    }
+
+    let unchecked = analysis.unchecked_features();
+    if !unchecked.is_empty() {
+        eprintln!("note: type checking was skipped, library features other than macros were not checked, nor were:");
+        for feature in unchecked {
+            eprintln!("    {}", feature.name);
+        }
+    }
+
+    for name in analysis.partial_crates() {
+        eprintln!("warning: `{}` failed to compile, its analysis may be incomplete", name);
+    }
+    for (path, reason) in analysis.skipped_cargo_files() {
+        eprintln!("warning: {} was skipped: {}", path.display(), reason);
+    }
+
     Ok(())
 }
+
+fn print_coverage(report: &CoverageReport) {
+    let width = report.features.iter().map(|f| f.name.len()).max().unwrap_or(0);
+    for feature in &report.features {
+        println!("{:<width$}  {:<7}  {}", feature.name, feature.since.to_string(), feature.detection, width = width);
+    }
+    if let Some(warning) = &report.warning {
+        eprintln!("warning: {}", warning);
+    }
+}
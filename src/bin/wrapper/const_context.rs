@@ -0,0 +1,118 @@
+use rustc_hir as hir;
+use rustc_hir::def_id::DefId;
+use rustc_hir::intravisit::{self, NestedVisitorMap, Visitor as _};
+use rustc_middle::hir::map::Map;
+use rustc_middle::ty::{self, TyCtxt};
+use rustc_span::symbol::{sym, Symbol};
+
+use super::context::StabCtxt;
+
+// Lang features that are detected in the bodies of constants, statics and `const fn`s.
+// NOTE: Only `const_let` is stable in the pinned compiler, the rest are reported as nightly-only.
+pub const LANG_FEATURES: &[Symbol] = &[sym::const_fn, sym::const_if_match, sym::const_let, sym::const_loop];
+
+struct Visitor<'a, 'scx, 'tcx> {
+    stab_ctx: &'a mut StabCtxt<'scx>,
+    tcx: TyCtxt<'tcx>,
+}
+
+impl<'tcx> intravisit::Visitor<'tcx> for Visitor<'_, '_, 'tcx> {
+    type Map = Map<'tcx>;
+
+    // Closures and nested items are not evaluated along with the body that contains them.
+    fn nested_visit_map(&mut self) -> NestedVisitorMap<Self::Map> {
+        NestedVisitorMap::None
+    }
+
+    fn visit_stmt(&mut self, stmt: &'tcx hir::Stmt<'tcx>) {
+        if let hir::StmtKind::Local(..) | hir::StmtKind::Semi(..) = stmt.kind {
+            self.stab_ctx.record_lang_feature(sym::const_let, stmt.span);
+        }
+
+        intravisit::walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'tcx hir::Expr<'tcx>) {
+        match expr.kind {
+            hir::ExprKind::Assign(..) | hir::ExprKind::AssignOp(..) => {
+                self.stab_ctx.record_lang_feature(sym::const_let, expr.span);
+            },
+            hir::ExprKind::Match(_, _, source) => match source {
+                hir::MatchSource::Normal
+                | hir::MatchSource::IfDesugar { .. }
+                | hir::MatchSource::IfLetDesugar { .. } => {
+                    self.stab_ctx.record_lang_feature(sym::const_if_match, expr.span);
+                },
+                // Loop conditions are reported along with their loop, and the rest are not written by the user.
+                _ => {},
+            },
+            hir::ExprKind::Loop(_, _, source) => match source {
+                hir::LoopSource::Loop | hir::LoopSource::While | hir::LoopSource::WhileLet => {
+                    self.stab_ctx.record_lang_feature(sym::const_loop, expr.span);
+                },
+                // NOTE: `for` loops call trait methods, which can't be done in const contexts yet.
+                hir::LoopSource::ForLoop => {},
+            },
+            _ => {},
+        }
+
+        intravisit::walk_expr(self, expr);
+    }
+}
+
+// `const fn`s could only be generic over `Sized` types. Like rustc's `min_const_fn` checks, only bounds on type
+// parameters count, including those of the parent impl or trait.
+fn check_trait_bounds(stab_ctx: &mut StabCtxt, tcx: TyCtxt, def_id: DefId) {
+    let sized_trait = tcx.lang_items().sized_trait();
+    let mut current = Some(def_id);
+    while let Some(def_id) = current {
+        let predicates = tcx.predicates_of(def_id);
+        for (predicate, _) in predicates.predicates {
+            let trait_predicate = match predicate {
+                ty::Predicate::Trait(trait_predicate, _) if Some(trait_predicate.def_id()) != sized_trait => {
+                    trait_predicate
+                },
+                _ => continue,
+            };
+            if let ty::Param(param) = trait_predicate.skip_binder().self_ty().kind {
+                let param_def = tcx.generics_of(def_id).type_param(&param, tcx);
+                stab_ctx.record_lang_feature(sym::const_fn, tcx.def_span(param_def.def_id));
+            }
+        }
+        current = predicates.parent;
+    }
+}
+
+/// Returns whether the node is inside the body of a `const fn`.
+// NOTE: Only the closest function counts, items nested in the body of a `const fn` are not evaluated with it.
+pub fn in_const_fn(tcx: TyCtxt<'_>, hir_id: hir::HirId) -> bool {
+    use hir::{ImplItem, ImplItemKind, Item, ItemKind, Node};
+
+    for (_, node) in tcx.hir().parent_iter(hir_id) {
+        match node {
+            Node::Item(&Item { kind: ItemKind::Fn(ref sig, ..), .. })
+            | Node::ImplItem(&ImplItem { kind: ImplItemKind::Fn(ref sig, ..), .. }) => return sig.header.is_const(),
+            _ => {},
+        }
+    }
+    false
+}
+
+/// Checks the bodies that are evaluated at compile time, i.e. those of constants, statics and `const fn`s
+/// (including array lengths and enum discriminants).
+pub fn process_crate(stab_ctx: &mut StabCtxt, tcx: TyCtxt) {
+    for &body_id in &tcx.hir().krate().body_ids {
+        let owner = tcx.hir().body_owner(body_id);
+        let body = tcx.hir().body(body_id);
+        // Anonymous constants (e.g. array lengths in ordinary functions) are constants of their own.
+        match tcx.hir().body_owner_kind(owner) {
+            hir::BodyOwnerKind::Const | hir::BodyOwnerKind::Static(_) => {},
+            hir::BodyOwnerKind::Fn if in_const_fn(tcx, body.value.hir_id) => {
+                check_trait_bounds(stab_ctx, tcx, tcx.hir().local_def_id(owner))
+            },
+            _ => continue,
+        }
+
+        Visitor { stab_ctx: &mut *stab_ctx, tcx }.visit_body(body);
+    }
+}
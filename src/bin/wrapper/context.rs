@@ -1,11 +1,12 @@
 use rustc_attr::Stability;
-use rustc_feature::ACCEPTED_FEATURES;
+use rustc_feature::{ACCEPTED_FEATURES, ACTIVE_FEATURES};
 use rustc_session::Session;
-use rustc_span::{source_map::SourceMap, symbol::Symbol, Span};
+use rustc_span::symbol::{sym, Symbol};
+use rustc_span::{edition, source_map::SourceMap, Span};
 
 use std::collections::{HashMap, HashSet};
 
-use cargo_minver::{Feature, FeatureKind};
+use cargo_minver::{Edition, Feature, FeatureKind, InactiveUse};
 
 use super::Wrapper;
 
@@ -13,11 +14,17 @@ pub struct StabCtxt<'a> {
     session: &'a Session,
     lang_features: HashMap<Symbol, HashSet<Span>>,
     lib_features: HashMap<Stability, HashSet<Span>>,
+    cargo_features: HashMap<Feature, HashSet<Span>>,
 }
 
 impl<'a> StabCtxt<'a> {
     pub fn new(session: &'a Session) -> Self {
-        Self { session, lang_features: Default::default(), lib_features: Default::default() }
+        Self {
+            session,
+            lang_features: Default::default(),
+            lib_features: Default::default(),
+            cargo_features: Default::default(),
+        }
     }
 
     pub fn record_lang_feature(&mut self, feature: Symbol, span: Span) {
@@ -28,11 +35,15 @@ impl<'a> StabCtxt<'a> {
         self.lib_features.entry(stab).or_default().insert(span);
     }
 
+    pub fn record_cargo_feature(&mut self, feature: Feature, span: Span) {
+        self.cargo_features.entry(feature).or_default().insert(span);
+    }
+
     pub fn dump(self, wrapper: &mut Wrapper) {
         macro_rules! add_features {
             ($wrapper: expr, $source_map: expr, $features: expr, $convert_op: expr) => {
                 for (elem, spans) in $features {
-                    let feature = $convert_op(*elem);
+                    let feature = $convert_op(elem);
                     $wrapper
                         .uses
                         .entry(feature.name.clone())
@@ -44,15 +55,31 @@ impl<'a> StabCtxt<'a> {
         }
 
         let source_map = self.session.source_map();
-        add_features!(wrapper, source_map, &self.lang_features, convert_lang_feature);
-        add_features!(wrapper, source_map, &self.lib_features, convert_lib_feature);
+        let edition = convert_edition(self.session.edition());
+        add_features!(wrapper, source_map, &self.lang_features, |name: &Symbol| convert_lang_feature(*name, edition));
+        add_features!(wrapper, source_map, &self.lib_features, |stab: &Stability| convert_lib_feature(*stab));
+        add_features!(wrapper, source_map, &self.cargo_features, |feature: &Feature| feature.clone());
+    }
+
+    pub fn dump_inactive(self, wrapper: &mut Wrapper, cfg: &str) {
+        let source_map = self.session.source_map();
+        let edition = convert_edition(self.session.edition());
+        for (name, spans) in &self.lang_features {
+            let feature = convert_lang_feature(*name, edition);
+            wrapper
+                .inactive_uses
+                .entry(feature.name.clone())
+                .or_default()
+                .extend(spans.iter().map(|s| InactiveUse { span: convert_span(source_map, *s), cfg: cfg.to_string() }));
+            wrapper.inactive_features.insert(feature);
+        }
     }
 }
 
 // We can't implement `From` for `Feature` and `Span` because of the orphan rules,
 // so the conversions are implemented here as free functions.
 
-fn convert_span(source_map: &SourceMap, span: rustc_span::Span) -> cargo_minver::Span {
+pub fn convert_span(source_map: &SourceMap, span: rustc_span::Span) -> cargo_minver::Span {
     let start = source_map.lookup_char_pos(span.lo());
     let end = source_map.lookup_char_pos(span.hi());
 
@@ -65,18 +92,44 @@ fn convert_span(source_map: &SourceMap, span: rustc_span::Span) -> cargo_minver:
     }
 }
 
-fn convert_lang_feature(name: Symbol) -> Feature {
-    // Special case the renamed `slice_patterns`.
-    fn maybe_min_slice_patterns(name: Symbol) -> Option<Feature> {
-        if name.to_string() == "min_slice_patterns" {
-            Some(Feature {
-                name: "min_slice_patterns".into(),
-                kind: FeatureKind::Lang,
-                since: Some("1.26.0".parse().unwrap()),
-            })
-        } else {
-            None
-        }
+pub fn convert_edition(edition: edition::Edition) -> Edition {
+    match edition {
+        edition::Edition::Edition2015 => Edition::Edition2015,
+        edition::Edition::Edition2018 => Edition::Edition2018,
+    }
+}
+
+/// Lang features that are tracked without a gate of their own, along with their stabilization version:
+/// `min_slice_patterns` is the former `slice_patterns`, which was renamed, while custom derives and the
+/// receivers allowed by `arbitrary_self_types` on stable were stabilized without a feature gate.
+const UNGATED_FEATURES: &[(&str, &str)] = &[
+    ("min_slice_patterns", "1.26.0"),
+    ("nested_receivers", "1.41.0"),
+    ("proc_macro_derive", "1.15.0"),
+    ("rc_arc_pin_receivers", "1.33.0"),
+];
+
+/// Lang features that are reported apart from the gate they were stabilized with, which gives their version:
+/// `proc_macro_attribute` shares the `proc_macro` gate with function-like macros.
+const SHARED_GATES: &[(&str, Symbol)] = &[("proc_macro_attribute", sym::proc_macro)];
+
+pub fn convert_lang_feature(name: Symbol, edition: Edition) -> Feature {
+    // Special case the features that have no gate in the feature table.
+    fn maybe_ungated(name: Symbol) -> Option<Feature> {
+        UNGATED_FEATURES.iter().find(|(feature, _)| name.as_str() == *feature).map(|(feature, since)| Feature {
+            name: feature.to_string(),
+            kind: FeatureKind::Lang,
+            since: Some(since.parse().unwrap()),
+        })
+    }
+
+    fn maybe_shared(name: Symbol) -> Option<Feature> {
+        let (feature, gate) = SHARED_GATES.iter().find(|(feature, _)| name.as_str() == *feature)?;
+        ACCEPTED_FEATURES.iter().find(|feat| feat.name == *gate).map(|feat| Feature {
+            name: feature.to_string(),
+            kind: FeatureKind::Lang,
+            since: Some(feat.since.parse().unwrap()),
+        })
     }
 
     ACCEPTED_FEATURES
@@ -85,9 +138,26 @@ fn convert_lang_feature(name: Symbol) -> Feature {
         .map(|feat| Feature {
             name: feat.name.to_string(),
             kind: FeatureKind::Lang,
-            since: Some(feat.since.parse().unwrap()),
+            since: Some(edition.feature_since(&*feat.name.as_str()).unwrap_or_else(|| feat.since.parse().unwrap())),
+        })
+        .or_else(|| maybe_shared(name))
+        .or_else(|| maybe_ungated(name))
+        .or_else(|| {
+            // Features that are only stable on some editions are not accepted yet.
+            edition.feature_since(&*name.as_str()).map(|since| Feature {
+                name: name.to_string(),
+                kind: FeatureKind::Lang,
+                since: Some(since),
+            })
+        })
+        .or_else(|| {
+            // Features that are not stable yet are nightly-only, like unstable lib features.
+            ACTIVE_FEATURES.iter().find(|feat| feat.name == name).map(|feat| Feature {
+                name: feat.name.to_string(),
+                kind: FeatureKind::Lang,
+                since: None,
+            })
         })
-        .or_else(|| maybe_min_slice_patterns(name))
         .unwrap()
 }
 
@@ -1,64 +1,172 @@
 #![feature(rustc_private)]
 #![feature(or_patterns)]
+#![feature(box_patterns)]
 
 extern crate rustc_ast;
+extern crate rustc_ast_lowering;
 extern crate rustc_attr;
 extern crate rustc_driver;
+extern crate rustc_expand;
 extern crate rustc_feature;
 extern crate rustc_hir;
 extern crate rustc_interface;
 extern crate rustc_middle;
+extern crate rustc_mir;
 extern crate rustc_parse;
 extern crate rustc_resolve;
 extern crate rustc_session;
 extern crate rustc_span;
 
+mod const_context;
 mod context;
+mod nll;
 mod post_analysis;
 mod post_expansion;
 mod pre_expansion;
+mod sysroot;
 
 use rustc_driver::{Callbacks, Compilation};
-use rustc_interface::interface::Compiler;
+use rustc_feature::ACCEPTED_FEATURES;
+use rustc_hir::def_id::LOCAL_CRATE;
+use rustc_interface::interface::{Compiler, Config};
 use rustc_interface::Queries;
+use rustc_middle::ty::query::Providers;
+use rustc_session::Session;
+use rustc_span::symbol::Symbol;
 
 use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 use std::process::Command;
-use std::{env, str};
+use std::{env, mem};
 
-use anyhow::{format_err, Context, Result};
+use anyhow::{bail, Context, Result};
 
 use cargo_minver::ipc::{self, Message};
-use cargo_minver::{CrateAnalysis, Feature, Span, SERVER_PORT_ENV};
+use cargo_minver::VERBOSE_ENV;
+use cargo_minver::{parse_rust_version, AcceptedFeature, ConditionalUse, CrateAnalysis, Edition, Feature, Gate};
+use cargo_minver::{InactiveUse, Span};
+use cargo_minver::{BEST_EFFORT_ENV, COVERAGE_ARG, FAST_ENV, PROBE_CFGS_ENV, RUSTC_WRAPPER_ENV, SERVER_PORT_ENV};
+use semver::Version;
 
 fn main() -> Result<()> {
     let mut args = env::args().collect::<Vec<_>>();
-    args.remove(1); // Remove "rustc" from the argument list
+    if args.get(1).map(String::as_str) == Some(COVERAGE_ARG) {
+        return print_coverage();
+    }
+    let rustc = args.remove(1); // Remove "rustc" from the argument list
 
-    if args.iter().any(|arg| arg == "--print=cfg") {
-        // Cargo is collecting information about the crate: passthrough to the actual compiler.
-        Command::new("rustc").args(&args[1..]).status().context("failed to execute rustc")?;
+    if is_passthrough(&args) {
+        // Cargo is collecting information about the compiler or the crate: passthrough to the actual compiler.
+        rustc_command(&rustc).args(&args[1..]).status().context("failed to execute rustc")?;
         Ok(())
     } else {
         // Cargo is building a crate: run the compiler using our wrapper.
-        args.extend(vec!["--sysroot".to_string(), fetch_sysroot().context("could not fetch sysroot")?]);
-        let mut wrapper = Wrapper::default();
-        rustc_driver::catch_fatal_errors(|| rustc_driver::run_compiler(&args, &mut wrapper, None, None).ok())
-            .map_err(|_| format_err!("compiler returned error exit status"))?;
+        let (sysroot, source) = sysroot::find(&args).context("could not find sysroot")?;
+        if env::var_os(VERBOSE_ENV).is_some() {
+            eprintln!("minver-wrapper: using sysroot {} (from {})", sysroot.display(), source);
+        }
+        if source != sysroot::Source::Arg {
+            args.extend(vec!["--sysroot".to_string(), sysroot.to_string_lossy().into_owned()]);
+        }
+        let mut wrapper = Wrapper {
+            manifest_dir: env::var_os("CARGO_MANIFEST_DIR").map(Into::into),
+            best_effort: env::var_os(BEST_EFFORT_ENV).is_some(),
+            fast: env::var_os(FAST_ENV).is_some(),
+            final_artifact: is_final_artifact(&args),
+            probe_cfgs: probe_cfgs_from_env(),
+            ..Default::default()
+        };
+        let result =
+            rustc_driver::catch_fatal_errors(|| rustc_driver::run_compiler(&args, &mut wrapper, None, None).ok());
+        if result.is_err() {
+            if !wrapper.best_effort {
+                bail!("compiler returned error exit status");
+            }
+            // Keep whatever was collected before the fatal error.
+            wrapper.partial = true;
+        }
 
         // Send the results to the server.
+        wrapper.split_conditional_uses();
+        let partial = wrapper.partial;
         let port = server_port_from_env().context("invalid server port in environment")?;
         let message = Message::AnalysisResult(CrateAnalysis::from(wrapper));
         ipc::send_message(port, &message).context("failed to send analysis result to server")?;
+
+        // Let cargo know the crate did not compile, even if we managed to analyze it.
+        if partial {
+            bail!("compiler returned error exit status");
+        }
         Ok(())
     }
 }
 
-// TODO: full-fledged sysroot detection (see e.g. clippy)
-fn fetch_sysroot() -> Result<String> {
-    let output = Command::new("rustc").args(vec!["--print", "sysroot"]).output()?;
-    let sysroot = str::from_utf8(&output.stdout)?;
-    Ok(sysroot.trim_end().to_string())
+// Lists the accepted lang features of the toolchain along with the passes that detect them.
+fn print_coverage() -> Result<()> {
+    let features = rustc_ast::with_default_globals(|| {
+        let nll_features = nll::features();
+        let passes: &[(&str, &[Symbol])] = &[
+            ("pre-expansion", pre_expansion::LANG_FEATURES),
+            ("post-expansion", post_expansion::LANG_FEATURES),
+            ("post-analysis", post_analysis::LANG_FEATURES),
+            ("const-context", const_context::LANG_FEATURES),
+            ("borrow-check", &nll_features),
+        ];
+
+        ACCEPTED_FEATURES
+            .iter()
+            .map(|feature| AcceptedFeature {
+                name: feature.name.to_string(),
+                since: feature.since.parse().unwrap(),
+                passes: passes
+                    .iter()
+                    .filter(|(_, features)| features.contains(&feature.name))
+                    .map(|(pass, _)| pass.to_string())
+                    .collect(),
+            })
+            .collect::<Vec<_>>()
+    });
+    println!("{}", serde_json::to_string(&features)?);
+    Ok(())
+}
+
+fn is_passthrough(args: &[String]) -> bool {
+    args.iter().any(|arg| arg.starts_with("--print") || arg == "-vV" || arg == "-V" || arg == "--version")
+}
+
+// In fast mode nothing is checked after expansion, so the analysis (type checking and borrow checking of every body,
+// lints, etc.) is skipped. Encoding the metadata still checks what it needs, e.g. the MIR of generic functions.
+fn skip_analysis(_session: &Session, local: &mut Providers<'_>, _external: &mut Providers<'_>) {
+    local.analysis = |_, _| Ok(());
+}
+
+// Binaries and tests are not used by other crates, unless they are build scripts which need to be linked.
+fn is_final_artifact(args: &[String]) -> bool {
+    let mut is_bin_or_test = false;
+    let mut emits_link = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--test" => is_bin_or_test = true,
+            "--crate-type" => is_bin_or_test |= args.next().map_or(false, |value| value == "bin"),
+            _ if arg.starts_with("--emit=") => emits_link |= arg["--emit=".len()..].split(',').any(|e| e == "link"),
+            _ => {},
+        }
+    }
+    is_bin_or_test && !emits_link
+}
+
+// If the user had a compiler wrapper configured, invocations we don't analyze must still go through it.
+fn rustc_command(rustc: &str) -> Command {
+    match env::var_os(RUSTC_WRAPPER_ENV) {
+        Some(wrapper) => {
+            let mut command = Command::new(wrapper);
+            command.arg(rustc);
+            command
+        },
+        None => Command::new(rustc),
+    }
 }
 
 fn server_port_from_env() -> Result<u16> {
@@ -67,18 +175,97 @@ fn server_port_from_env() -> Result<u16> {
     Ok(port)
 }
 
+// The driver passes the probe cfgs as a comma-separated list of `cfg=version` pairs.
+fn probe_cfgs_from_env() -> HashMap<String, Version> {
+    let probe_cfgs = env::var(PROBE_CFGS_ENV).unwrap_or_default();
+    probe_cfgs
+        .split(',')
+        .filter_map(|pair| {
+            let pos = pair.find('=')?;
+            Some((pair[..pos].to_string(), parse_rust_version(&pair[pos + 1..])?))
+        })
+        .collect()
+}
+
 #[derive(Debug, Default)]
 pub struct Wrapper {
     crate_name: String,
+    manifest_dir: Option<PathBuf>,
+    edition: Edition,
+    best_effort: bool,
+    fast: bool,
+    final_artifact: bool,
+    partial: bool,
     features: HashSet<Feature>,
+    unchecked: Vec<Feature>,
     uses: HashMap<String, HashSet<Span>>,
+    inactive_features: HashSet<Feature>,
+    inactive_uses: HashMap<String, HashSet<InactiveUse>>,
+    probe_cfgs: HashMap<String, Version>,
+    gates: Vec<(Span, Gate)>,
+    conditional_features: HashSet<Feature>,
+    conditional_uses: HashMap<String, HashSet<ConditionalUse>>,
+}
+
+impl Wrapper {
+    // In best-effort mode errors don't stop the analysis, but the results are flagged as partial.
+    fn check_errors(&mut self, session: &Session) {
+        if !self.best_effort {
+            session.abort_if_errors();
+        } else if session.has_errors() {
+            self.partial = true;
+        }
+    }
+
+    // Uses in gated code don't count towards the minimum version, as older compilers don't see them.
+    // They are reported apart so that the gates can be checked against the features they protect.
+    fn split_conditional_uses(&mut self) {
+        if self.gates.is_empty() {
+            return;
+        }
+
+        for feature in mem::take(&mut self.features) {
+            let uses = self.uses.remove(&feature.name).unwrap_or_default();
+            let (gated, ungated): (HashSet<_>, HashSet<_>) =
+                uses.into_iter().partition(|span| self.gates.iter().any(|(gated_span, _)| gated_span.contains(span)));
+
+            if !gated.is_empty() {
+                let conditional_uses = self.conditional_uses.entry(feature.name.clone()).or_default();
+                for span in gated {
+                    // With nested gates, the one that requires the newest compiler applies.
+                    let gate = self
+                        .gates
+                        .iter()
+                        .filter(|(gated_span, _)| gated_span.contains(&span))
+                        .map(|(_, gate)| gate)
+                        .max_by(|a, b| a.version.cmp(&b.version))
+                        .unwrap();
+                    conditional_uses.insert(ConditionalUse { span, gate: gate.clone() });
+                }
+                self.conditional_features.insert(feature.clone());
+                if ungated.is_empty() {
+                    continue;
+                }
+            }
+
+            self.uses.insert(feature.name.clone(), ungated);
+            self.features.insert(feature);
+        }
+    }
 }
 
 impl Callbacks for Wrapper {
+    fn config(&mut self, config: &mut Config) {
+        config.override_queries = Some(if self.fast { skip_analysis } else { nll::override_queries });
+    }
+
     fn after_parsing<'tcx>(&mut self, compiler: &Compiler, queries: &'tcx Queries<'tcx>) -> Compilation {
         let session = compiler.session();
-        session.abort_if_errors();
+        self.check_errors(session);
 
+        self.crate_name = queries.crate_name().unwrap().peek().clone();
+        self.edition = context::convert_edition(session.edition());
+        self.features.extend(self.edition.feature());
         let krate = &*queries.parse().unwrap().peek();
         pre_expansion::process_crate(self, session, krate);
 
@@ -87,12 +274,53 @@ impl Callbacks for Wrapper {
 
     fn after_expansion<'tcx>(&mut self, compiler: &Compiler, queries: &'tcx Queries<'tcx>) -> Compilation {
         let session = compiler.session();
-        session.abort_if_errors();
+        self.check_errors(session);
+
+        {
+            let (krate, boxed_resolver, ..) = &*queries.expansion().unwrap().peek();
+            boxed_resolver.borrow().borrow_mut().access(|resolver| {
+                post_expansion::process_crate(self, session, krate, resolver);
+            });
+        }
+
+        if self.fast {
+            // NOTE: Some features are detected by the syntactic passes too, so they are still checked.
+            let syntactic =
+                pre_expansion::LANG_FEATURES.iter().chain(post_expansion::LANG_FEATURES).collect::<HashSet<_>>();
+            self.unchecked = post_analysis::LANG_FEATURES
+                .iter()
+                .chain(const_context::LANG_FEATURES)
+                .chain(&nll::features())
+                .filter(|f| !syntactic.contains(f))
+                .map(|f| context::convert_lang_feature(*f, self.edition))
+                .collect();
 
-        let (krate, boxed_resolver, ..) = &*queries.expansion().unwrap().peek();
-        boxed_resolver.borrow().borrow_mut().access(|resolver| {
-            post_expansion::process_crate(self, session, krate, resolver);
-        });
+            // NOTE: Crates other crates depend on must keep going for rustc to emit their metadata (without the
+            // analysis, see `skip_analysis`), but there's no need to do so for the final artifacts. Cargo still
+            // expects the dep-info file though, which is written when preparing the outputs.
+            if self.final_artifact {
+                let _ = queries.prepare_outputs();
+                return Compilation::Stop;
+            }
+            return Compilation::Continue;
+        }
+
+        if self.best_effort {
+            // Type information can't be trusted if the crate failed to parse or expand.
+            if self.partial {
+                return Compilation::Stop;
+            }
+
+            // NOTE: `after_analysis` is not called if type checking fails, so in best-effort mode the analysis
+            // is driven from here. Bodies that failed to typecheck are skipped by the post-analysis visitor.
+            match queries.global_ctxt() {
+                Ok(global_ctxt) => global_ctxt.peek_mut().enter(|tcx| {
+                    self.partial = tcx.analysis(LOCAL_CRATE).is_err();
+                    post_analysis::process_crate(self, tcx);
+                }),
+                Err(_) => self.partial = true,
+            }
+        }
 
         Compilation::Continue
     }
@@ -100,10 +328,11 @@ impl Callbacks for Wrapper {
     fn after_analysis<'tcx>(&mut self, compiler: &Compiler, queries: &'tcx Queries<'tcx>) -> Compilation {
         compiler.session().abort_if_errors();
 
-        self.crate_name = queries.crate_name().unwrap().peek().clone();
-        queries.global_ctxt().unwrap().peek_mut().enter(|tcx| {
-            post_analysis::process_crate(self, tcx);
-        });
+        if !self.best_effort && !self.fast {
+            queries.global_ctxt().unwrap().peek_mut().enter(|tcx| {
+                post_analysis::process_crate(self, tcx);
+            });
+        }
 
         Compilation::Continue
     }
@@ -113,8 +342,18 @@ impl From<Wrapper> for CrateAnalysis {
     fn from(wrapper: Wrapper) -> Self {
         CrateAnalysis {
             name: wrapper.crate_name,
+            manifest_dir: wrapper.manifest_dir,
+            // NOTE: The driver knows which target each analysis was run for.
+            target: None,
+            edition: wrapper.edition,
+            partial: wrapper.partial,
+            unchecked: wrapper.unchecked,
             features: wrapper.features.into_iter().collect(),
             uses: wrapper.uses.into_iter().map(|(k, v)| (k, v.into_iter().collect())).collect(),
+            inactive_features: wrapper.inactive_features.into_iter().collect(),
+            inactive_uses: wrapper.inactive_uses.into_iter().map(|(k, v)| (k, v.into_iter().collect())).collect(),
+            conditional_features: wrapper.conditional_features.into_iter().collect(),
+            conditional_uses: wrapper.conditional_uses.into_iter().map(|(k, v)| (k, v.into_iter().collect())).collect(),
         }
     }
 }
@@ -0,0 +1,201 @@
+use rustc_hir::def_id::DefId;
+use rustc_middle::mir::visit::{MutatingUseContext, NonMutatingUseContext, PlaceContext, Visitor};
+use rustc_middle::mir::{Body, BorrowCheckResult, BorrowKind, Local, Location, Place, ProjectionElem};
+use rustc_middle::mir::{Rvalue, StatementKind};
+use rustc_middle::ty::query::Providers;
+use rustc_middle::ty::TyCtxt;
+use rustc_session::Session;
+use rustc_span::symbol::Symbol;
+use rustc_span::Span;
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+
+// NOTE: Neither is an accepted feature, as NLL is still being phased in for the 2015 edition.
+// Their stabilization versions are edition-dependent (see `cargo_minver::Edition`).
+const FEATURES: &[&str] = &["nll", "two_phase_borrows"];
+
+/// Lang features that are detected by the borrow checker runs. Used to report what is not checked when the
+/// analysis is skipped.
+pub fn features() -> Vec<Symbol> {
+    FEATURES.iter().map(|feature| Symbol::intern(feature)).collect()
+}
+
+thread_local! {
+    // Queries are providers, i.e. plain functions, so the results are passed to `post_analysis` through here.
+    static USES: RefCell<Vec<(Symbol, Span)>> = RefCell::new(Vec::new());
+}
+
+// MIR is stolen by later passes, so it is inspected right before the borrow checker runs on it.
+pub fn override_queries(_session: &Session, local: &mut Providers<'_>, _external: &mut Providers<'_>) {
+    local.mir_borrowck = mir_borrowck;
+}
+
+fn mir_borrowck(tcx: TyCtxt<'_>, def_id: DefId) -> &BorrowCheckResult<'_> {
+    {
+        let body = tcx.mir_validated(def_id).0.borrow();
+        let uses = Checker { body: &*body }.check();
+        USES.with(|u| u.borrow_mut().extend(uses));
+    }
+
+    let mut providers = Providers::default();
+    rustc_mir::provide(&mut providers);
+    (providers.mir_borrowck)(tcx, def_id)
+}
+
+/// Returns the uses found by the borrow checker runs so far.
+pub fn take_uses() -> Vec<(Symbol, Span)> {
+    USES.with(|u| u.replace(Vec::new()))
+}
+
+struct Checker<'a, 'tcx> {
+    body: &'a Body<'tcx>,
+}
+
+impl<'a, 'tcx> Checker<'a, 'tcx> {
+    fn check(&self) -> Vec<(Symbol, Span)> {
+        let nll = Symbol::intern("nll");
+        let two_phase_borrows = Symbol::intern("two_phase_borrows");
+
+        let mut uses = Vec::new();
+        for (block, data) in self.body.basic_blocks().iter_enumerated() {
+            if data.is_cleanup {
+                continue;
+            }
+
+            for (statement_index, statement) in data.statements.iter().enumerate() {
+                let (lhs, kind, borrowed) = match &statement.kind {
+                    StatementKind::Assign(box (lhs, Rvalue::Ref(_, kind, borrowed))) => (lhs, *kind, borrowed),
+                    _ => continue,
+                };
+                let location = Location { block, statement_index };
+
+                if let BorrowKind::Mut { allow_two_phase_borrow: true } = kind {
+                    // The reservation is activated by the first use of the temporary that holds it, usually a call.
+                    // Two-phase borrows are needed if the borrowed place is read in between, e.g. `v.push(v.len())`.
+                    let temp = lhs.local;
+                    let activations =
+                        self.find_uses(location, |p, context| p.local == temp && !is_ignored(context), |_| false);
+                    let reads = self.find_uses(
+                        location,
+                        |p, context| overlaps(p, borrowed) && !is_ignored(context),
+                        |l| activations.contains(&l),
+                    );
+                    if !reads.is_empty() {
+                        let span = activations.first().map_or(statement.source_info.span, |l| self.span(*l));
+                        uses.push((two_phase_borrows, span));
+                    }
+                } else if lhs.projection.is_empty() && self.body.local_decls[lhs.local].is_user_variable() {
+                    // A lexical borrow lives until the reference goes out of scope. Any conflicting access of the
+                    // borrowed place before that compiles only because the borrow ends with the last use of the
+                    // reference.
+                    // NOTE: References derived from the borrow (e.g. returned by a method) are not tracked.
+                    let reference = lhs.local;
+                    let conflicts = self.find_uses(
+                        location,
+                        |p, context| overlaps(p, borrowed) && !is_ignored(context) && conflicts(kind, context),
+                        |l| self.is_storage_dead(l, reference),
+                    );
+                    uses.extend(conflicts.into_iter().map(|l| (nll, self.span(l))));
+                }
+            }
+        }
+        uses
+    }
+
+    // Walks the control flow graph from `start`, without going past the locations where `stop` holds,
+    // and returns the locations that use a place that satisfies `filter`.
+    fn find_uses<F, S>(&self, start: Location, filter: F, stop: S) -> Vec<Location>
+    where
+        F: Fn(&Place<'tcx>, PlaceContext) -> bool,
+        S: Fn(Location) -> bool,
+    {
+        let mut found = Vec::new();
+        // NOTE: The block of the borrow is not walked again if there's a loop, so that it doesn't conflict with itself.
+        let mut visited = vec![start.block].into_iter().collect::<HashSet<_>>();
+        let mut pending = vec![(start.block, start.statement_index + 1)];
+        while let Some((block, first)) = pending.pop() {
+            let data = &self.body.basic_blocks()[block];
+            let mut stopped = false;
+            for statement_index in first..=data.statements.len() {
+                let location = Location { block, statement_index };
+                if stop(location) {
+                    stopped = true;
+                    break;
+                }
+
+                let mut collector = PlaceCollector { places: Vec::new() };
+                match data.statements.get(statement_index) {
+                    Some(statement) => collector.visit_statement(statement, location),
+                    None => collector.visit_terminator(data.terminator(), location),
+                }
+                if collector.places.iter().any(|(p, context)| filter(p, *context)) {
+                    found.push(location);
+                }
+            }
+
+            if !stopped {
+                for successor in data.terminator().successors() {
+                    if !self.body.basic_blocks()[*successor].is_cleanup && visited.insert(*successor) {
+                        pending.push((*successor, 0));
+                    }
+                }
+            }
+        }
+        found.sort();
+        found
+    }
+
+    fn is_storage_dead(&self, location: Location, local: Local) -> bool {
+        match self.body.basic_blocks()[location.block].statements.get(location.statement_index) {
+            Some(statement) => match statement.kind {
+                StatementKind::StorageDead(dead) => dead == local,
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    fn span(&self, location: Location) -> Span {
+        self.body.source_info(location).span
+    }
+}
+
+struct PlaceCollector<'tcx> {
+    places: Vec<(Place<'tcx>, PlaceContext)>,
+}
+
+impl<'tcx> Visitor<'tcx> for PlaceCollector<'tcx> {
+    fn visit_place(&mut self, place: &Place<'tcx>, context: PlaceContext, _location: Location) {
+        self.places.push((*place, context));
+    }
+}
+
+// Drops run when the borrow is already dead, and storage markers are not accesses.
+fn is_ignored(context: PlaceContext) -> bool {
+    match context {
+        PlaceContext::MutatingUse(MutatingUseContext::Drop | MutatingUseContext::Retag) => true,
+        _ => context.is_storage_marker() || !context.is_use(),
+    }
+}
+
+fn conflicts(kind: BorrowKind, context: PlaceContext) -> bool {
+    match kind {
+        BorrowKind::Shared | BorrowKind::Shallow => {
+            context.is_mutating_use() || context == PlaceContext::NonMutatingUse(NonMutatingUseContext::Move)
+        },
+        BorrowKind::Unique | BorrowKind::Mut { .. } => true,
+    }
+}
+
+// Places overlap unless they diverge at a field of the same value. Indexes are conservatively assumed to overlap.
+fn overlaps(a: &Place<'_>, b: &Place<'_>) -> bool {
+    a.local == b.local
+        && a.projection.iter().zip(b.projection.iter()).all(|(a, b)| {
+            a == b
+                || match (a, b) {
+                    (ProjectionElem::Field(..), ProjectionElem::Field(..)) => false,
+                    _ => true,
+                }
+        })
+}
@@ -6,14 +6,45 @@ use rustc_hir::def_id::{DefId, CRATE_DEF_INDEX, LOCAL_CRATE};
 use rustc_hir::intravisit::{self, NestedVisitorMap};
 use rustc_hir::pat_util::EnumerateAndAdjustIterator;
 use rustc_middle::hir::map::Map;
-use rustc_middle::ty::{self, TyCtxt, TypeckTables};
+use rustc_middle::ty::adjustment::{Adjust, Adjustment, PointerCast};
+use rustc_middle::ty::{self, TyCtxt, TypeFoldable, TypeckTables};
 use rustc_session::config::EntryFnType;
-use rustc_span::symbol::{sym, Ident};
+use rustc_span::symbol::{sym, Ident, Symbol};
 use rustc_span::Span;
 
+use std::collections::HashMap;
+use std::iter;
 use std::mem;
 
-use super::{context::StabCtxt, Wrapper};
+use super::{const_context, context::StabCtxt, nll, Wrapper};
+
+// Lang features that are (at least partially) detected by this pass. Used to report what is not checked
+// when type checking is skipped.
+pub const LANG_FEATURES: &[Symbol] = &[
+    sym::arbitrary_self_types,
+    sym::augmented_assignments,
+    sym::bind_by_move_pattern_guards,
+    sym::braced_empty_structs,
+    sym::clone_closures,
+    sym::closure_to_fn_coercion,
+    sym::const_constructor,
+    sym::copy_closures,
+    sym::exhaustive_integer_patterns,
+    sym::i128_type,
+    sym::impl_header_lifetime_elision,
+    sym::infer_outlives_requirements,
+    sym::irrefutable_let_patterns,
+    sym::match_default_bindings,
+    sym::min_const_unsafe_fn,
+    sym::more_struct_aliases,
+    sym::pub_restricted,
+    sym::re_rebalance_coherence,
+    sym::relaxed_adts,
+    sym::self_in_typedefs,
+    sym::self_struct_ctor,
+    sym::termination_trait,
+    sym::type_alias_enum_variants,
+];
 
 struct Visitor<'a, 'scx, 'tcx> {
     stab_ctx: &'a mut StabCtxt<'scx>,
@@ -21,11 +52,20 @@ struct Visitor<'a, 'scx, 'tcx> {
     tables: &'a TypeckTables<'tcx>,
     empty_tables: &'a TypeckTables<'tcx>,
     visiting_adt_def: bool,
+    // Uses of local closures in the crate, along with whether they move or copy the closure.
+    closure_uses: HashMap<hir::HirId, Vec<(Span, hir::HirId, bool)>>,
 }
 
 impl<'a, 'scx, 'tcx> Visitor<'a, 'scx, 'tcx> {
     pub fn new(stab_ctx: &'a mut StabCtxt<'scx>, tcx: TyCtxt<'tcx>, empty_tables: &'a TypeckTables<'tcx>) -> Self {
-        Visitor { stab_ctx, tcx, tables: empty_tables, empty_tables, visiting_adt_def: false }
+        Visitor {
+            stab_ctx,
+            tcx,
+            tables: empty_tables,
+            empty_tables,
+            visiting_adt_def: false,
+            closure_uses: HashMap::new(),
+        }
     }
 
     fn process_lib_stability(&mut self, def_id: DefId, span: Span) {
@@ -38,7 +78,7 @@ impl<'a, 'scx, 'tcx> Visitor<'a, 'scx, 'tcx> {
         }
     }
 
-    fn process_match(&mut self, expr: &hir::Expr, arms: &[hir::Arm], source: hir::MatchSource) {
+    fn process_match(&mut self, expr: &hir::Expr, scrutinee: &hir::Expr, arms: &[hir::Arm], source: hir::MatchSource) {
         let tcx = self.tcx;
         let pat_adjustments = self.tables.pat_adjustments();
 
@@ -72,6 +112,25 @@ impl<'a, 'scx, 'tcx> Visitor<'a, 'scx, 'tcx> {
                 self.stab_ctx.record_lang_feature(sym::irrefutable_let_patterns, pat.span);
             }
         }
+
+        if let hir::MatchSource::Normal = source {
+            self.check_exhaustive_integer_patterns(expr, scrutinee, arms);
+        }
+    }
+
+    // Matches on integers used to need a catch-all arm, so a match that compiles without one is only
+    // exhaustive because of its ranges and literals.
+    // NOTE: Half-open range patterns (`a..b` and `a..`) are not stable in the pinned compiler.
+    fn check_exhaustive_integer_patterns(&mut self, expr: &hir::Expr, scrutinee: &hir::Expr, arms: &[hir::Arm]) {
+        let is_integer = match self.tables.expr_ty(scrutinee).peel_refs().kind {
+            ty::Int(_) | ty::Uint(_) | ty::Char => true,
+            _ => false,
+        };
+        let has_catch_all = arms.iter().any(|arm| arm.guard.is_none() && !arm.pat.is_refutable());
+
+        if is_integer && !has_catch_all {
+            self.stab_ctx.record_lang_feature(sym::exhaustive_integer_patterns, expr.span);
+        }
     }
 
     fn process_struct(&mut self, ty_kind: &ty::TyKind, res: Res, span: Span) {
@@ -120,6 +179,156 @@ impl<'a, 'scx, 'tcx> Visitor<'a, 'scx, 'tcx> {
         }
     }
 
+    // The outlives requirements of an ADT that are not spelled out were inferred from the types of its fields.
+    // NOTE: `infer_static_outlives_requirements` is not stable in the pinned compiler, so `'static` requirements
+    // are never inferred.
+    fn check_inferred_outlives(&mut self, item: &hir::Item) {
+        let def_id = self.tcx.hir().local_def_id(item.hir_id);
+        let explicit = self.tcx.explicit_predicates_of(def_id);
+        for (predicate, span) in self.tcx.inferred_outlives_of(def_id) {
+            if !explicit.predicates.iter().any(|(p, _)| p == predicate) {
+                let span = if span.is_dummy() { item.span } else { *span };
+                self.stab_ctx.record_lang_feature(sym::infer_outlives_requirements, span);
+            }
+        }
+    }
+
+    fn check_receiver(&mut self, hir_id: hir::HirId) {
+        let tcx = self.tcx;
+        let def_id = tcx.hir().local_def_id(hir_id);
+        let item = tcx.associated_item(def_id);
+        if !item.fn_has_self_parameter {
+            return;
+        }
+
+        let self_ty = match item.container {
+            ty::ImplContainer(impl_def_id) => tcx.type_of(impl_def_id),
+            ty::TraitContainer(_) => tcx.types.self_param,
+        };
+        let receiver_ty = tcx.fn_sig(def_id).input(0).skip_binder();
+        let feature = match classify_receiver(tcx, receiver_ty, self_ty) {
+            Some(Receiver::Plain) | None => return,
+            Some(Receiver::RcArcPin) => Symbol::intern("rc_arc_pin_receivers"),
+            Some(Receiver::Nested) => Symbol::intern("nested_receivers"),
+            Some(Receiver::Arbitrary) => sym::arbitrary_self_types,
+        };
+
+        let span = match tcx.hir().maybe_body_owned_by(hir_id) {
+            Some(body_id) => tcx.hir().body(body_id).params[0].span,
+            None => match tcx.hir().fn_decl_by_hir_id(hir_id) {
+                Some(decl) => decl.inputs[0].span,
+                None => tcx.def_span(def_id),
+            },
+        };
+        self.stab_ctx.record_lang_feature(feature, span);
+    }
+
+    fn check_closure_to_fn_coercion(&mut self, expr: &hir::Expr) {
+        let coerced = self.tables.expr_adjustments(expr).iter().any(|adjustment| match adjustment.kind {
+            Adjust::Pointer(PointerCast::ClosureFnPointer(_)) => true,
+            _ => false,
+        });
+        if coerced {
+            self.stab_ctx.record_lang_feature(sym::closure_to_fn_coercion, expr.span);
+        }
+    }
+
+    // The `Self` type of the call is the type that gets cloned, e.g. `(&&closure).clone()` clones a reference.
+    fn check_clone_closure(&mut self, def_id: DefId, callee_id: hir::HirId, expr: &hir::Expr) {
+        let is_clone = self.tcx.lang_items().clone_trait().map_or(false, |clone| {
+            self.tcx.trait_of_item(def_id) == Some(clone) && self.tcx.item_name(def_id) == sym::clone
+        });
+        let self_ty = self.tables.node_substs(callee_id).types().next();
+        if let (true, Some(self_ty)) = (is_clone, self_ty) {
+            if let ty::Closure(..) = self_ty.kind {
+                self.stab_ctx.record_lang_feature(sym::clone_closures, expr.span);
+            }
+        }
+    }
+
+    // Whether a closure is copied depends on the uses that come after, so they are only collected here
+    // (see `check_copy_closures`).
+    fn collect_closure_use(&mut self, expr: &hir::Expr) {
+        let local = match expr.kind {
+            hir::ExprKind::Path(hir::QPath::Resolved(None, path)) => match path.res {
+                Res::Local(local) => local,
+                _ => return,
+            },
+            _ => return,
+        };
+        match self.tables.expr_ty_opt(expr).map(|ty| &ty.kind) {
+            Some(ty::Closure(..)) => {},
+            _ => return,
+        }
+
+        let by_value = match self.tables.expr_adjustments(expr).first() {
+            // Coercing a closure to a function pointer does not need it to be `Copy`.
+            Some(Adjustment { kind: Adjust::Borrow(_) | Adjust::Pointer(_), .. }) => false,
+            _ => {
+                let parent_id = self.tcx.hir().get_parent_node(expr.hir_id);
+                match self.tcx.hir().find(parent_id) {
+                    Some(hir::Node::Expr(parent)) => match parent.kind {
+                        hir::ExprKind::AddrOf(..) => false,
+                        hir::ExprKind::Call(callee, _) => callee.hir_id != expr.hir_id,
+                        _ => true,
+                    },
+                    _ => true,
+                }
+            },
+        };
+        self.closure_uses.entry(local).or_default().push((expr.span, expr.hir_id, by_value));
+    }
+
+    // A closure that is moved and used again afterwards must have been copied.
+    // NOTE: Uses are ordered by their position in the source, so copies due to loops are missed.
+    fn check_copy_closures(&mut self) {
+        let mut closure_uses = mem::take(&mut self.closure_uses);
+        for uses in closure_uses.values_mut() {
+            uses.sort_by_key(|(span, ..)| span.lo());
+            for (pos, (span, hir_id, by_value)) in uses.iter().enumerate() {
+                let used_again =
+                    uses[pos + 1..].iter().any(|(_, later_id, _)| !self.in_exclusive_arms(*hir_id, *later_id));
+                if *by_value && used_again {
+                    self.stab_ctx.record_lang_feature(sym::copy_closures, *span);
+                }
+            }
+        }
+    }
+
+    // Whether the nodes are in different arms of the same `match` (including the branches of an `if`),
+    // so that only one of them runs.
+    fn in_exclusive_arms(&self, a: hir::HirId, b: hir::HirId) -> bool {
+        let hir = self.tcx.hir();
+        let is_arm = |id| matches!(hir.find(id), Some(hir::Node::Arm(_)));
+        let a_ancestors = iter::once(a).chain(hir.parent_iter(a).map(|(id, _)| id)).collect::<Vec<_>>();
+
+        let mut b_child = b;
+        for (id, _) in hir.parent_iter(b) {
+            if let Some(pos) = a_ancestors.iter().position(|&a_id| a_id == id) {
+                return pos > 0 && is_arm(a_ancestors[pos - 1]) && is_arm(b_child);
+            }
+            b_child = id;
+        }
+        false
+    }
+
+    // Before 1.41, the types that come before the first local one in an impl of a foreign trait could not
+    // contain any type parameter, even if covered by another type (e.g. `impl<T> From<Local> for Vec<T>`).
+    fn check_re_rebalance_coherence(&mut self, item: &hir::Item) {
+        let def_id = self.tcx.hir().local_def_id(item.hir_id);
+        let trait_ref = match self.tcx.impl_trait_ref(def_id) {
+            Some(trait_ref) if !trait_ref.def_id.is_local() => trait_ref,
+            _ => return,
+        };
+
+        let types = trait_ref.substs.types().collect::<Vec<_>>();
+        if let Some(first_local) = types.iter().position(|ty| is_local_type(self.tcx, *ty)) {
+            if types[..first_local].iter().any(|ty| ty.has_param_types()) {
+                self.stab_ctx.record_lang_feature(sym::re_rebalance_coherence, self.tcx.def_span(def_id));
+            }
+        }
+    }
+
     fn check_alias_enum_variants(&mut self, qpath: &hir::QPath, hir_id: hir::HirId, span: Span) {
         let res = self.tables.qpath_res(qpath, hir_id);
 
@@ -136,7 +345,7 @@ impl<'a, 'scx, 'tcx> Visitor<'a, 'scx, 'tcx> {
 
     fn check_min_const_unsafe_fn(&mut self, def_id: DefId, expr: &hir::Expr) {
         if let hir::Unsafety::Unsafe = self.tcx.fn_sig(def_id).unsafety() {
-            if self.in_const_fn(expr.hir_id) {
+            if const_context::in_const_fn(self.tcx, expr.hir_id) {
                 self.stab_ctx.record_lang_feature(sym::min_const_unsafe_fn, expr.span);
             }
         }
@@ -156,27 +365,15 @@ impl<'a, 'scx, 'tcx> Visitor<'a, 'scx, 'tcx> {
         let tables =
             if self.tcx.has_typeck_tables(def_id) { self.tcx.typeck_tables_of(def_id) } else { self.empty_tables };
 
+        // NOTE: This only happens in best-effort mode. Tables of bodies that failed to typecheck may be incomplete.
+        if tables.tainted_by_errors {
+            return;
+        }
+
         let old_tables = mem::replace(&mut self.tables, tables);
         f(self);
         self.tables = old_tables;
     }
-
-    fn in_const_fn(&self, hir_id: hir::HirId) -> bool {
-        use hir::{ImplItem, ImplItemKind, Item, ItemKind, Node};
-
-        for (_, node) in self.tcx.hir().parent_iter(hir_id) {
-            match node {
-                Node::Item(&Item { kind: ItemKind::Fn(ref sig, ..), .. })
-                | Node::ImplItem(&ImplItem { kind: ImplItemKind::Fn(ref sig, ..), .. })
-                    if sig.header.is_const() =>
-                {
-                    return true;
-                }
-                _ => {},
-            }
-        }
-        false
-    }
 }
 
 impl<'tcx> intravisit::Visitor<'tcx> for Visitor<'_, '_, 'tcx> {
@@ -202,6 +399,8 @@ impl<'tcx> intravisit::Visitor<'tcx> for Visitor<'_, '_, 'tcx> {
                 self.process_lib_stability(def_id, item.span);
             },
             hir::ItemKind::Impl { ref of_trait, items, ref generics, .. } => {
+                self.check_re_rebalance_coherence(item);
+
                 for param in generics.params {
                     if let hir::GenericParamKind::Lifetime { kind: hir::LifetimeParamKind::Elided } = param.kind {
                         self.stab_ctx.record_lang_feature(sym::impl_header_lifetime_elision, param.span);
@@ -226,6 +425,7 @@ impl<'tcx> intravisit::Visitor<'tcx> for Visitor<'_, '_, 'tcx> {
                 }
             },
             hir::ItemKind::Enum(..) | hir::ItemKind::Struct(..) | hir::ItemKind::Union(..) => {
+                self.check_inferred_outlives(item);
                 self.visiting_adt_def = true;
             },
             _ => {},
@@ -238,12 +438,14 @@ impl<'tcx> intravisit::Visitor<'tcx> for Visitor<'_, '_, 'tcx> {
     }
 
     fn visit_impl_item(&mut self, impl_item: &'tcx hir::ImplItem<'tcx>) {
+        self.check_receiver(impl_item.hir_id);
         self.with_item_tables(impl_item.hir_id, |v| {
             intravisit::walk_impl_item(v, impl_item);
         })
     }
 
     fn visit_trait_item(&mut self, trait_item: &'tcx hir::TraitItem<'tcx>) {
+        self.check_receiver(trait_item.hir_id);
         self.with_item_tables(trait_item.hir_id, |v| {
             intravisit::walk_trait_item(v, trait_item);
         })
@@ -291,12 +493,15 @@ impl<'tcx> intravisit::Visitor<'tcx> for Visitor<'_, '_, 'tcx> {
     }
 
     fn visit_expr(&mut self, expr: &'tcx hir::Expr<'tcx>) {
+        self.check_closure_to_fn_coercion(expr);
+
         match expr.kind {
             hir::ExprKind::Call(path, _) => {
                 if let hir::Expr { kind: hir::ExprKind::Path(qpath), .. } = path {
                     if let Res::Def(_, def_id) = self.tables.qpath_res(qpath, path.hir_id) {
                         self.check_min_const_unsafe_fn(def_id, expr);
                         self.check_const_constructor(def_id, expr);
+                        self.check_clone_closure(def_id, path.hir_id, expr);
                     }
                 }
             },
@@ -305,6 +510,7 @@ impl<'tcx> intravisit::Visitor<'tcx> for Visitor<'_, '_, 'tcx> {
                     self.process_lib_stability(def_id, expr.span);
                     self.check_min_const_unsafe_fn(def_id, expr);
                     self.check_const_constructor(def_id, expr);
+                    self.check_clone_closure(def_id, expr.hir_id, expr);
                 }
             },
             hir::ExprKind::Field(subexpr, ident) => {
@@ -329,6 +535,7 @@ impl<'tcx> intravisit::Visitor<'tcx> for Visitor<'_, '_, 'tcx> {
             },
             hir::ExprKind::Path(ref qpath) => {
                 self.check_alias_enum_variants(qpath, expr.hir_id, expr.span);
+                self.collect_closure_use(expr);
             },
             hir::ExprKind::AssignOp(_, lhs, rhs) => {
                 let lhs_ty = self.tables.expr_ty_adjusted_opt(lhs);
@@ -340,8 +547,8 @@ impl<'tcx> intravisit::Visitor<'tcx> for Visitor<'_, '_, 'tcx> {
                     }
                 }
             },
-            hir::ExprKind::Match(_, arms, source) => {
-                self.process_match(expr, arms, source);
+            hir::ExprKind::Match(scrutinee, arms, source) => {
+                self.process_match(expr, scrutinee, arms, source);
             },
             _ => {},
         }
@@ -381,6 +588,77 @@ impl<'tcx> intravisit::Visitor<'tcx> for Visitor<'_, '_, 'tcx> {
     }
 }
 
+// Local types for the orphan rules, including fundamental types (e.g. `&T` or `Box<T>`) of local types.
+fn is_local_type<'tcx>(tcx: TyCtxt<'tcx>, ty: ty::Ty<'tcx>) -> bool {
+    match ty.kind {
+        ty::Ref(_, inner, _) => is_local_type(tcx, inner),
+        ty::Adt(def, substs) if def.is_fundamental() => substs.types().next().map_or(false, |t| is_local_type(tcx, t)),
+        ty::Adt(def, _) => def.did.is_local(),
+        ty::Foreign(def_id) => def_id.is_local(),
+        ty::Dynamic(predicates, _) => predicates.principal_def_id().map_or(false, |def_id| def_id.is_local()),
+        _ => false,
+    }
+}
+
+enum Receiver {
+    /// `self`, `&self`, `&mut self` and `self: Box<Self>`.
+    Plain,
+    /// `Rc<Self>`, `Arc<Self>`, or `Pin<P>` where `P` is a plain receiver other than `Self` or one of the former.
+    RcArcPin,
+    /// Any other combination of references, `Box`, `Rc`, `Arc` and `Pin`, such as `&Box<Self>`.
+    Nested,
+    /// Receivers that need `arbitrary_self_types`, such as raw pointers or user-defined smart pointers.
+    Arbitrary,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pointer {
+    Ref,
+    Box,
+    Rc,
+    Arc,
+    Pin,
+    Other,
+}
+
+// Classifies a receiver type by the pointers it goes through to get to `Self`.
+fn classify_receiver<'tcx>(tcx: TyCtxt<'tcx>, receiver_ty: ty::Ty<'tcx>, self_ty: ty::Ty<'tcx>) -> Option<Receiver> {
+    let mut pointers = Vec::new();
+    let mut ty = receiver_ty;
+    while ty != self_ty {
+        let (pointer, inner) = match ty.kind {
+            ty::Ref(_, inner, _) => (Pointer::Ref, inner),
+            ty::RawPtr(ty::TypeAndMut { ty: inner, .. }) => (Pointer::Other, inner),
+            ty::Adt(def, substs) if substs.types().next().is_some() => {
+                let pointer = if def.is_box() {
+                    Pointer::Box
+                } else if tcx.is_diagnostic_item(sym::Rc, def.did) {
+                    Pointer::Rc
+                } else if tcx.is_diagnostic_item(sym::Arc, def.did) {
+                    Pointer::Arc
+                } else if tcx.lang_items().pin_type() == Some(def.did) {
+                    Pointer::Pin
+                } else {
+                    Pointer::Other
+                };
+                (pointer, substs.type_at(0))
+            },
+            _ => return None,
+        };
+        pointers.push(pointer);
+        ty = inner;
+    }
+
+    let receiver = match pointers.as_slice() {
+        [] | [Pointer::Ref] | [Pointer::Box] => Receiver::Plain,
+        [Pointer::Rc] | [Pointer::Arc] => Receiver::RcArcPin,
+        [Pointer::Pin, Pointer::Ref | Pointer::Box | Pointer::Rc | Pointer::Arc] => Receiver::RcArcPin,
+        _ if pointers.contains(&Pointer::Other) => Receiver::Arbitrary,
+        _ => Receiver::Nested,
+    };
+    Some(receiver)
+}
+
 fn check_termination_trait(stab_ctx: &mut StabCtxt, tcx: TyCtxt) {
     if let Some((main_did, EntryFnType::Main)) = tcx.entry_fn(LOCAL_CRATE) {
         let hir_id = tcx.hir().as_local_hir_id(main_did).unwrap();
@@ -406,6 +684,14 @@ pub fn process_crate(wrapper: &mut Wrapper, tcx: TyCtxt) {
     let empty_tables = TypeckTables::empty(None);
     let mut visitor = Visitor::new(&mut stab_ctx, tcx, &empty_tables);
     tcx.hir().krate().visit_all_item_likes(&mut visitor.as_deep_visitor());
+    visitor.check_copy_closures();
+
+    const_context::process_crate(&mut stab_ctx, tcx);
+
+    // The borrow checker has already run as part of the analysis.
+    for (feature, span) in nll::take_uses() {
+        stab_ctx.record_lang_feature(feature, span);
+    }
 
     stab_ctx.dump(wrapper);
 }
@@ -1,35 +1,187 @@
 use rustc_ast::ast::{self, Pat, RangeEnd, RangeSyntax};
 use rustc_ast::ptr::P;
+use rustc_ast::token::{BinOpToken, Token, TokenKind};
+use rustc_ast::tokenstream::{TokenStream, TokenTree};
 use rustc_ast::visit::{self, FnKind};
+use rustc_ast_lowering::Resolver as _;
 use rustc_attr::{self as attr, Stability, StabilityLevel};
+use rustc_expand::base::SyntaxExtensionKind;
+use rustc_hir::def::{DefKind, Res};
+use rustc_hir::def_id::CRATE_DEF_INDEX;
 use rustc_resolve::{ParentScope, Resolver};
 use rustc_session::lint::Level;
 use rustc_session::Session;
-use rustc_span::hygiene::{ExpnData, ExpnKind};
+use rustc_span::hygiene::{ExpnData, ExpnKind, MacroKind};
 use rustc_span::source_map::{SourceMap, Spanned};
 use rustc_span::symbol::{kw, sym, Symbol};
+use rustc_span::FileName;
 use rustc_span::Span;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use super::{context::StabCtxt, Wrapper};
 
-struct Visitor<'a, 'scx, 'res> {
+// Lang features that are (at least partially) detected by this pass. `min_slice_patterns`, `proc_macro_attribute`
+// and `proc_macro_derive` are not accepted features (see `context::convert_lang_feature`).
+pub const LANG_FEATURES: &[Symbol] = &[
+    sym::abi_sysv64,
+    sym::associated_consts,
+    sym::async_await,
+    sym::braced_empty_structs,
+    sym::conservative_impl_trait,
+    sym::const_indexing,
+    sym::crate_in_paths,
+    sym::deprecated,
+    sym::dotdot_in_tuple_patterns,
+    sym::dotdoteq_in_patterns,
+    sym::dyn_trait,
+    sym::extern_absolute_paths,
+    sym::extern_crate_item_prelude,
+    sym::extern_crate_self,
+    sym::extern_prelude,
+    sym::field_init_shorthand,
+    sym::fn_must_use,
+    sym::generic_param_attrs,
+    sym::i128_type,
+    sym::if_while_or_patterns,
+    sym::inclusive_range_syntax,
+    sym::item_like_imports,
+    sym::loop_break_value,
+    sym::macro_at_most_once_rep,
+    sym::macro_lifetime_matcher,
+    sym::macro_literal_matcher,
+    sym::macro_vis_matcher,
+    sym::min_const_fn,
+    sym::no_std,
+    sym::non_exhaustive,
+    sym::non_modrs_mods,
+    sym::panic_handler,
+    sym::param_attrs,
+    sym::pattern_parentheses,
+    sym::proc_macro,
+    sym::proc_macro_path_invoc,
+    sym::question_mark,
+    sym::raw_identifiers,
+    sym::relaxed_adts,
+    sym::repr_align,
+    sym::repr_align_enum,
+    sym::repr_packed,
+    sym::repr_transparent,
+    sym::slice_patterns,
+    sym::static_in_const,
+    sym::struct_field_attributes,
+    sym::target_feature,
+    sym::tool_attributes,
+    sym::tool_lints,
+    sym::transparent_enums,
+    sym::underscore_const_names,
+    sym::underscore_imports,
+    sym::underscore_lifetimes,
+    sym::uniform_paths,
+    sym::universal_impl_trait,
+    sym::use_extern_macros,
+    sym::use_nested_groups,
+    sym::used,
+    sym::windows_subsystem,
+];
+
+pub struct Visitor<'a, 'scx, 'res> {
     stab_ctx: &'a mut StabCtxt<'scx>,
-    resolver: &'a mut Resolver<'res>,
+    // NOTE: There's no resolver when visiting code that won't be expanded (see `pre_expansion`).
+    resolver: Option<&'a mut Resolver<'res>>,
     source_map: &'a SourceMap,
     imported_macros: HashMap<Symbol, Option<Stability>>,
+    // Whether each macro used by the crate is a proc macro, by kind and path from the crate root.
+    proc_macros: HashMap<(MacroKind, Vec<Symbol>), bool>,
+    // The file of the crate root, whose out-of-line modules are always in the layout of `mod.rs` files.
+    root_file: Option<FileName>,
+    // Names of the `extern crate` items of the crate root, along with whether they rename the crate.
+    extern_crates: HashMap<Symbol, bool>,
+    // The modules being visited, from the crate root to the innermost one.
+    modules: Vec<ModuleScope>,
+    in_import: bool,
     // NOTE: `advanced_slice_patterns` was renamed to `slice_patterns`, so we need a new symbol to track the former feature.
     min_slice_patterns: Symbol,
 }
 
+#[derive(Default)]
+struct ModuleScope {
+    // Names brought into scope by `use` items, which shadow the extern prelude.
+    // NOTE: Imports in blocks are added to the module that contains them, which can only hide uses.
+    imports: HashSet<Symbol>,
+    // The paths of the names brought into scope by `use` items, as written (e.g. relative to the crate root on 2015).
+    import_paths: HashMap<Symbol, Vec<Symbol>>,
+    has_private_items: bool,
+}
+
+impl ModuleScope {
+    fn new(module: &ast::Mod) -> Self {
+        let mut scope = ModuleScope::default();
+        for item in &module.items {
+            match &item.kind {
+                ast::ItemKind::Use(use_tree) => scope.add_imports(use_tree),
+                ast::ItemKind::ExternCrate(..) => {
+                    scope.imports.insert(item.ident.name);
+                },
+                // Only items with a name can be imported, so `impl` and `extern` blocks don't count.
+                ast::ItemKind::Fn(..)
+                | ast::ItemKind::Const(..)
+                | ast::ItemKind::Static(..)
+                | ast::ItemKind::Struct(..)
+                | ast::ItemKind::Enum(..)
+                | ast::ItemKind::Union(..)
+                | ast::ItemKind::Trait(..)
+                | ast::ItemKind::TyAlias(..)
+                | ast::ItemKind::Mod(..) => {
+                    scope.has_private_items |= matches!(item.vis.node, ast::VisibilityKind::Inherited);
+                },
+                _ => {},
+            }
+        }
+        scope
+    }
+
+    fn add_imports(&mut self, use_tree: &ast::UseTree) {
+        self.add_imports_with_prefix(use_tree, &[]);
+    }
+
+    fn add_imports_with_prefix(&mut self, use_tree: &ast::UseTree, prefix: &[Symbol]) {
+        let mut path = prefix.to_vec();
+        path.extend(use_tree.prefix.segments.iter().map(|segment| segment.ident.name));
+        match &use_tree.kind {
+            ast::UseTreeKind::Simple(..) => {
+                let name = use_tree.ident().name;
+                self.imports.insert(name);
+                // `use foo::{self}` imports `foo` itself.
+                if path.last() == Some(&kw::SelfLower) {
+                    path.pop();
+                }
+                self.import_paths.insert(name, path);
+            },
+            ast::UseTreeKind::Nested(use_trees) => {
+                use_trees.iter().for_each(|(tree, _)| self.add_imports_with_prefix(tree, &path));
+            },
+            ast::UseTreeKind::Glob => {},
+        }
+    }
+}
+
 impl<'a, 'scx, 'res> Visitor<'a, 'scx, 'res> {
-    fn new(stab_ctx: &'a mut StabCtxt<'scx>, resolver: &'a mut Resolver<'res>, source_map: &'a SourceMap) -> Self {
+    pub fn new(
+        stab_ctx: &'a mut StabCtxt<'scx>,
+        resolver: Option<&'a mut Resolver<'res>>,
+        source_map: &'a SourceMap,
+    ) -> Self {
         Self {
             stab_ctx,
             resolver,
             source_map,
             imported_macros: Default::default(),
+            proc_macros: Default::default(),
+            root_file: None,
+            extern_crates: Default::default(),
+            modules: Vec::new(),
+            in_import: false,
             min_slice_patterns: Symbol::intern("min_slice_patterns"),
         }
     }
@@ -127,6 +279,318 @@ impl<'a, 'scx, 'res> Visitor<'a, 'scx, 'res> {
         }
     }
 
+    fn check_macro_export(&mut self, item: &ast::Item) {
+        let metas = item.attrs.iter().filter(|a| a.has_name(sym::macro_export)).filter_map(|a| a.meta_item_list());
+        for meta in metas.flatten() {
+            if meta.has_name(sym::local_inner_macros) {
+                // NOTE: There's no feature gate for `local_inner_macros`, it was stabilized along with the macro
+                // modularization.
+                self.stab_ctx.record_lang_feature(sym::use_extern_macros, meta.span());
+            }
+        }
+    }
+
+    // Rules are `matcher => transcriber`, separated by semicolons. Only the matchers declare fragments.
+    fn check_macro_rules(&mut self, body: &ast::MacArgs) {
+        let mut trees = body.inner_tokens().trees();
+        while let Some(tree) = trees.next() {
+            if let TokenTree::Delimited(_, _, matcher) = tree {
+                self.check_macro_matcher(matcher);
+                // Skip `=>` and the transcriber.
+                trees.next();
+                trees.next();
+            }
+        }
+    }
+
+    fn check_macro_matcher(&mut self, matcher: TokenStream) {
+        let mut trees = matcher.trees().peekable();
+        while let Some(tree) = trees.next() {
+            let dollar = match tree {
+                TokenTree::Token(Token { kind: TokenKind::Dollar, span }) => span,
+                TokenTree::Delimited(_, _, inner) => {
+                    self.check_macro_matcher(inner);
+                    continue;
+                },
+                _ => continue,
+            };
+
+            match trees.next() {
+                // `$name:fragment`
+                Some(TokenTree::Token(token)) if token.is_ident() => {
+                    if let Some(TokenTree::Token(Token { kind: TokenKind::Colon, .. })) = trees.peek() {
+                        trees.next();
+                        if let Some(TokenTree::Token(fragment)) = trees.next() {
+                            let feature = match fragment.ident() {
+                                Some((ident, false)) if ident.name == sym::lifetime => sym::macro_lifetime_matcher,
+                                Some((ident, false)) if ident.name == sym::vis => sym::macro_vis_matcher,
+                                Some((ident, false)) if ident.name == sym::literal => sym::macro_literal_matcher,
+                                _ => continue,
+                            };
+                            self.stab_ctx.record_lang_feature(feature, dollar.to(fragment.span));
+                        }
+                    }
+                },
+                // `$(...) sep? op`
+                Some(TokenTree::Delimited(_, _, inner)) => {
+                    self.check_macro_matcher(inner);
+                    if let Some(TokenTree::Token(Token { kind: TokenKind::Question, span })) = trees.peek().cloned() {
+                        trees.next();
+                        // A `?` followed by another operator is a separator.
+                        match trees.peek() {
+                            Some(TokenTree::Token(Token {
+                                kind: TokenKind::BinOp(BinOpToken::Star | BinOpToken::Plus),
+                                ..
+                            })) => {},
+                            _ => self.stab_ctx.record_lang_feature(sym::macro_at_most_once_rep, dollar.to(span)),
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    fn check_proc_macro_def(&mut self, item: &ast::Item) {
+        for attr in &item.attrs {
+            if attr.has_name(sym::proc_macro_derive) {
+                self.stab_ctx.record_lang_feature(sym::proc_macro_derive, attr.span);
+            } else if attr.has_name(sym::proc_macro_attribute) {
+                self.stab_ctx.record_lang_feature(sym::proc_macro_attribute, attr.span);
+            } else if attr.has_name(sym::proc_macro) {
+                self.stab_ctx.record_lang_feature(sym::proc_macro, attr.span);
+            }
+        }
+    }
+
+    // Invocations are gone after expansion, so proc macros are found through the items they expand to.
+    // Items that keep the spans of the input (e.g. the item an attribute is applied to, when it is passed through)
+    // are found through the expansion that defined them instead.
+    // NOTE: Only items are checked, as proc macros can't be expanded anywhere else without `proc_macro_hygiene`,
+    // which is not stable in the pinned compiler.
+    fn check_proc_macro_use(&mut self, item: &ast::Item) {
+        let resolver = match &mut self.resolver {
+            Some(resolver) => resolver,
+            None => return,
+        };
+        let ExpnData { kind, call_site, .. } = if item.span.from_expansion() {
+            item.span.ctxt().outer_expn_data()
+        } else {
+            let definitions = resolver.definitions();
+            match definitions.opt_local_def_id(item.id) {
+                Some(def_id) => definitions.expansion_that_defined(def_id.index).expn_data(),
+                None => return,
+            }
+        };
+        let (kind, path) = match kind {
+            ExpnKind::Macro(kind, path) => (kind, path),
+            _ => return,
+        };
+
+        // Macros are resolved from the crate root, so the ones imported into the module of the invocation are
+        // resolved through their import path instead.
+        // NOTE: Imports relative to the module itself (`self::` and `super::` ones) are not followed.
+        let mut root_path = path.as_str().split("::").map(Symbol::intern).collect::<Vec<_>>();
+        let import_path = self.modules.last().and_then(|module| module.import_paths.get(&root_path[0]));
+        if let Some(import_path) = import_path {
+            if !import_path.is_empty() && ![kw::SelfLower, kw::Super].contains(&import_path[0]) {
+                root_path.splice(..1, import_path.iter().cloned());
+            }
+        }
+
+        let is_proc_macro = *self.proc_macros.entry((kind, root_path.clone())).or_insert_with(|| {
+            let segments =
+                root_path.iter().map(|name| ast::PathSegment::from_ident(ast::Ident::new(*name, call_site))).collect();
+            let path = ast::Path { span: call_site, segments };
+            let scope = ParentScope::module(resolver.graph_root());
+            match resolver.resolve_macro_path(&path, Some(kind), &scope, false, false) {
+                Ok((Some(ext), ..)) => match ext.kind {
+                    SyntaxExtensionKind::Bang(..) | SyntaxExtensionKind::Attr(..) | SyntaxExtensionKind::Derive(..) => {
+                        true
+                    },
+                    _ => false,
+                },
+                _ => false,
+            }
+        });
+        if !is_proc_macro {
+            return;
+        }
+
+        let feature = match kind {
+            MacroKind::Derive => sym::proc_macro_derive,
+            MacroKind::Attr => sym::proc_macro_attribute,
+            MacroKind::Bang => sym::proc_macro,
+        };
+        self.stab_ctx.record_lang_feature(feature, call_site);
+        if path.as_str().contains("::") {
+            self.stab_ctx.record_lang_feature(sym::proc_macro_path_invoc, call_site);
+        }
+    }
+
+    // `foo.rs` may declare out-of-line modules, which are then loaded from `foo/`.
+    fn check_non_modrs_mod(&mut self, item: &ast::Item, module: &ast::Mod) {
+        if module.inline || item.attrs.iter().any(|a| a.has_name(sym::path)) {
+            return;
+        }
+
+        let file = self.source_map.span_to_filename(item.span);
+        let is_modrs = match &file {
+            FileName::Real(path) => path.file_name().map_or(false, |name| name == "mod.rs"),
+            _ => true,
+        };
+        if !is_modrs && self.root_file.as_ref() != Some(&file) {
+            self.stab_ctx.record_lang_feature(sym::non_modrs_mods, item.span);
+        }
+    }
+
+    fn is_extern_crate_root(&mut self, segment: &ast::PathSegment) -> bool {
+        let resolver = match &mut self.resolver {
+            Some(resolver) => resolver,
+            None => return false,
+        };
+        match resolver.get_partial_res(segment.id).map(|res| res.base_res()) {
+            Some(Res::Def(DefKind::Mod, def_id)) => !def_id.is_local() && def_id.index == CRATE_DEF_INDEX,
+            _ => false,
+        }
+    }
+
+    // Imports are classified by the first segment of their path, which is where the rules changed.
+    fn check_import(&mut self, use_tree: &ast::UseTree, node_id: ast::NodeId) {
+        if use_tree.span.from_expansion() {
+            return;
+        }
+
+        let segments = &use_tree.prefix.segments;
+        let (first, has_root) = match segments.first() {
+            Some(segment) if segment.ident.name == kw::PathRoot => (segments.get(1), true),
+            first => (first, false),
+        };
+
+        match first {
+            Some(first) => match first.ident.name {
+                kw::Crate | kw::SelfLower | kw::Super | kw::DollarCrate => {},
+                _ if use_tree.span.rust_2018() => {
+                    // Local names are shadowed by extern crates in 2018 imports, unless they have `uniform_paths`.
+                    if !has_root && !self.is_extern_crate_root(first) {
+                        self.stab_ctx.record_lang_feature(sym::uniform_paths, first.ident.span);
+                    }
+                },
+                name => {
+                    // 2015 imports are relative to the crate root, where only `extern crate` items used to be.
+                    if !self.extern_crates.contains_key(&name) && self.is_extern_crate_root(first) {
+                        self.stab_ctx.record_lang_feature(sym::extern_absolute_paths, first.ident.span);
+                    }
+                },
+            },
+            None => {
+                // `use {a, b};` or `use ::{a, b};`.
+                if let ast::UseTreeKind::Nested(use_trees) = &use_tree.kind {
+                    for (tree, id) in use_trees {
+                        self.check_import(tree, *id);
+                    }
+                }
+                return;
+            },
+        }
+
+        self.check_glob_import(use_tree);
+        self.check_macro_import(use_tree, node_id);
+    }
+
+    // Glob imports used to only import public items. Importing from an ancestor module is a sure sign that
+    // private items are imported too.
+    // NOTE: Glob imports shadowed by other items, which were also allowed by `item_like_imports`, are not detected.
+    fn check_glob_import(&mut self, use_tree: &ast::UseTree) {
+        if let ast::UseTreeKind::Simple(..) | ast::UseTreeKind::Nested(..) = use_tree.kind {
+            return;
+        }
+
+        let segments = &use_tree.prefix.segments;
+        let ancestor = match segments.first().map(|s| s.ident.name) {
+            Some(kw::Super) if segments.iter().all(|s| s.ident.name == kw::Super) => {
+                self.modules.len().checked_sub(segments.len() + 1)
+            },
+            Some(kw::Crate) if segments.len() == 1 => Some(0),
+            _ => return,
+        };
+        if ancestor.and_then(|i| self.modules.get(i)).map_or(false, |module| module.has_private_items) {
+            self.stab_ctx.record_lang_feature(sym::item_like_imports, use_tree.span);
+        }
+    }
+
+    fn check_macro_import(&mut self, use_tree: &ast::UseTree, node_id: ast::NodeId) {
+        match &use_tree.kind {
+            ast::UseTreeKind::Simple(..) => {
+                let resolver = match &mut self.resolver {
+                    Some(resolver) => resolver,
+                    None => return,
+                };
+                // Names that are also bound in another namespace, like `std::env` or `std::vec`, are mostly imported
+                // for their module, so only imports of macros alone are reported.
+                let res = resolver.get_import_res(node_id);
+                let is_macro = matches!(res.macro_ns, Some(Res::Def(DefKind::Macro(_), _)));
+                if is_macro && res.type_ns.is_none() && res.value_ns.is_none() {
+                    self.stab_ctx.record_lang_feature(sym::use_extern_macros, use_tree.span);
+                }
+            },
+            ast::UseTreeKind::Nested(use_trees) => {
+                for (tree, id) in use_trees {
+                    self.check_macro_import(tree, *id);
+                }
+            },
+            ast::UseTreeKind::Glob => {},
+        }
+    }
+
+    // Paths outside of imports can name crates that are not imported, through the extern prelude.
+    // NOTE: The extern prelude is implied by the 2018 edition, so only 2015 paths are checked.
+    fn check_path(&mut self, path: &ast::Path) {
+        if self.in_import || path.span.from_expansion() || path.span.rust_2018() {
+            return;
+        }
+
+        let segments = &path.segments;
+        let (first, has_root) = match segments.first() {
+            Some(segment) if segment.ident.name == kw::PathRoot => (segments.get(1), true),
+            first => (first, false),
+        };
+        let first = match first {
+            Some(first) if segments.len() > 1 && self.is_extern_crate_root(first) => first,
+            _ => return,
+        };
+
+        let name = first.ident.name;
+        let feature = if has_root {
+            // Absolute paths used to only see the `extern crate` items of the crate root.
+            if self.extern_crates.contains_key(&name) {
+                return;
+            }
+            sym::extern_absolute_paths
+        } else {
+            let in_root = self.modules.len() == 1;
+            let imported = self.modules.last().map_or(false, |module| module.imports.contains(&name));
+            match self.extern_crates.get(&name) {
+                _ if imported => return,
+                Some(_) if in_root => return,
+                // Crates renamed by `extern crate` items are not passed with `--extern`.
+                Some(true) => sym::extern_crate_item_prelude,
+                Some(false) | None => sym::extern_prelude,
+            }
+        };
+        self.stab_ctx.record_lang_feature(feature, first.ident.span);
+    }
+
+    // Macros can be invoked by path, instead of being imported with `#[macro_use]`.
+    fn check_macro_path(&mut self, span: Span) {
+        let ExpnData { kind, call_site, .. } = span.ctxt().outer_expn_data();
+        if let ExpnKind::Macro(MacroKind::Bang, path) = kind {
+            if !call_site.from_expansion() && path.as_str().contains("::") {
+                self.stab_ctx.record_lang_feature(sym::use_extern_macros, call_site);
+            }
+        }
+    }
+
     fn check_macro_use(&mut self, span: Span) {
         if !span.from_expansion() {
             return;
@@ -137,7 +601,10 @@ impl<'a, 'scx, 'res> Visitor<'a, 'scx, 'res> {
                 return;
             }
 
-            let resolver = &mut self.resolver;
+            let resolver = match &mut self.resolver {
+                Some(resolver) => resolver,
+                None => return,
+            };
             let maybe_stab = self.imported_macros.entry(name).or_insert_with(|| {
                 let path = ast::Path::from_ident(ast::Ident::new(name, def_site));
                 let scope = ParentScope::module(resolver.graph_root());
@@ -178,7 +645,38 @@ impl<'ast> visit::Visitor<'ast> for Visitor<'_, '_, '_> {
             self.stab_ctx.record_lang_feature(sym::underscore_imports, span);
         }
 
-        visit::walk_use_tree(self, use_tree, node_id);
+        if nested {
+            visit::walk_use_tree(self, use_tree, node_id);
+        } else {
+            self.check_import(use_tree, node_id);
+            if let Some(module) = self.modules.last_mut() {
+                module.add_imports(use_tree);
+            }
+
+            self.in_import = true;
+            visit::walk_use_tree(self, use_tree, node_id);
+            self.in_import = false;
+        }
+    }
+
+    fn visit_mod(&mut self, module: &ast::Mod, span: Span, _attrs: &[ast::Attribute], node_id: ast::NodeId) {
+        if node_id == ast::CRATE_NODE_ID {
+            self.root_file = Some(self.source_map.span_to_filename(span));
+            for item in &module.items {
+                if let ast::ItemKind::ExternCrate(original) = item.kind {
+                    self.extern_crates.insert(item.ident.name, original.map_or(false, |name| name != item.ident.name));
+                }
+            }
+        }
+
+        self.modules.push(ModuleScope::new(module));
+        visit::walk_mod(self, module);
+        self.modules.pop();
+    }
+
+    fn visit_path(&mut self, path: &ast::Path, _node_id: ast::NodeId) {
+        self.check_path(path);
+        visit::walk_path(self, path);
     }
 
     fn visit_attribute(&mut self, attr: &ast::Attribute) {
@@ -217,6 +715,8 @@ impl<'ast> visit::Visitor<'ast> for Visitor<'_, '_, '_> {
 
     fn visit_item(&mut self, item: &ast::Item) {
         self.check_macro_use(item.span);
+        self.check_proc_macro_use(item);
+        self.check_macro_path(item.span);
 
         match &item.kind {
             ast::ItemKind::ExternCrate(original) => {
@@ -227,6 +727,15 @@ impl<'ast> visit::Visitor<'ast> for Visitor<'_, '_, '_> {
                     self.stab_ctx.record_lang_feature(sym::extern_crate_self, item.span);
                 }
             },
+            ast::ItemKind::Mod(module) => {
+                self.check_non_modrs_mod(item, module);
+            },
+            ast::ItemKind::MacroDef(macro_def) => {
+                if macro_def.macro_rules {
+                    self.check_macro_export(item);
+                    self.check_macro_rules(&macro_def.body);
+                }
+            },
             ast::ItemKind::ForeignMod(foreign_mod) => {
                 if let Some(abi) = foreign_mod.abi {
                     self.check_abi_sysv64(&abi);
@@ -262,6 +771,7 @@ impl<'ast> visit::Visitor<'ast> for Visitor<'_, '_, '_> {
                 if item.attrs.iter().any(|a| a.has_name(sym::must_use)) {
                     self.stab_ctx.record_lang_feature(sym::fn_must_use, item.span);
                 }
+                self.check_proc_macro_def(item);
             },
             ast::ItemKind::Impl { items, .. } => {
                 for impl_item in items {
@@ -365,6 +875,7 @@ impl<'ast> visit::Visitor<'ast> for Visitor<'_, '_, '_> {
 
     fn visit_stmt(&mut self, stmt: &ast::Stmt) {
         self.check_macro_use(stmt.span);
+        self.check_macro_path(stmt.span);
         visit::walk_stmt(self, stmt);
     }
 
@@ -375,6 +886,7 @@ impl<'ast> visit::Visitor<'ast> for Visitor<'_, '_, '_> {
 
     fn visit_expr(&mut self, expr: &ast::Expr) {
         self.check_macro_use(expr.span);
+        self.check_macro_path(expr.span);
 
         match &expr.kind {
             ast::ExprKind::Range(_, _, ast::RangeLimits::Closed) => {
@@ -487,7 +999,7 @@ impl<'ast> visit::Visitor<'ast> for Visitor<'_, '_, '_> {
 
 pub fn process_crate(wrapper: &mut Wrapper, session: &Session, krate: &ast::Crate, resolver: &mut Resolver) {
     let mut stab_ctx = StabCtxt::new(session);
-    let mut visitor = Visitor::new(&mut stab_ctx, resolver, session.source_map());
+    let mut visitor = Visitor::new(&mut stab_ctx, Some(resolver), session.source_map());
     visit::walk_crate(&mut visitor, &krate);
 
     let raw_ident_spans = session.parse_sess.raw_identifier_spans.borrow();
@@ -1,9 +1,32 @@
-use rustc_ast::{ast, visit};
+use rustc_ast::ast;
+use rustc_ast::attr::HasAttrs;
+use rustc_ast::token::{self, TokenKind};
+use rustc_ast::tokenstream::TokenTree;
+use rustc_ast::visit::{self, AssocCtxt, Visitor as _};
 use rustc_parse::{self, MACRO_ARGUMENTS};
-use rustc_session::{parse::ParseSess, Session};
+use rustc_session::Session;
 use rustc_span::symbol::{sym, Symbol};
+use rustc_span::Span;
 
-use super::{context::StabCtxt, Wrapper};
+use std::collections::HashMap;
+use std::mem;
+
+use cargo_minver::{directive_features, parse_rust_version, Gate};
+use semver::Version;
+
+use super::context::{self, StabCtxt};
+use super::{post_expansion, Wrapper};
+
+// Lang features that are (at least partially) detected by this pass.
+pub const LANG_FEATURES: &[Symbol] = &[
+    sym::cfg_attr_multi,
+    sym::cfg_doctest,
+    sym::cfg_target_feature,
+    sym::cfg_target_vendor,
+    sym::generic_param_attrs,
+    sym::param_attrs,
+    sym::struct_field_attributes,
+];
 
 // NOTE: This visitor is intended to be used only to catch active attributes before they are removed,
 // but the approach is not valid as it won't catch attributes generated as a result of macro expansion.
@@ -11,14 +34,156 @@ use super::{context::StabCtxt, Wrapper};
 
 struct Visitor<'a, 'scx> {
     stab_ctx: &'a mut StabCtxt<'scx>,
-    parse_sess: &'a ParseSess,
+    session: &'scx Session,
     // NOTE: sym::target_vendor does not exist
     target_vendor: Symbol,
+    // Features used in code disabled by a `cfg`, along with the predicate that disables it.
+    inactive: Vec<(String, StabCtxt<'scx>)>,
+    in_inactive_cfg: bool,
+    probe_cfgs: &'a HashMap<String, Version>,
+    build_script: bool,
+    // Nodes that only some compilers will see, because of `rustversion` attributes or probe cfgs.
+    gates: Vec<(cargo_minver::Span, Gate)>,
 }
 
 impl<'a, 'scx> Visitor<'a, 'scx> {
-    fn new(stab_ctx: &'a mut StabCtxt<'scx>, parse_sess: &'a ParseSess) -> Self {
-        Self { stab_ctx, parse_sess, target_vendor: Symbol::intern("target_vendor") }
+    fn new(
+        stab_ctx: &'a mut StabCtxt<'scx>,
+        session: &'scx Session,
+        probe_cfgs: &'a HashMap<String, Version>,
+        build_script: bool,
+    ) -> Self {
+        Self {
+            stab_ctx,
+            session,
+            target_vendor: Symbol::intern("target_vendor"),
+            inactive: Vec::new(),
+            in_inactive_cfg: false,
+            probe_cfgs,
+            build_script,
+            gates: Vec::new(),
+        }
+    }
+
+    // Build scripts talk to cargo by printing directives, some of which need a newer cargo.
+    // NOTE: Only literals are checked here, the driver checks what build scripts actually print.
+    fn check_build_script_directive(&mut self, mac: &ast::MacCall) {
+        let segments = &mac.path.segments;
+        if !self.build_script || segments.len() != 1 || &*segments[0].ident.as_str() != "println" {
+            return;
+        }
+
+        if let Some(TokenTree::Token(tok)) = mac.args.inner_tokens().trees().next() {
+            let (kind, symbol) = match tok.kind {
+                TokenKind::Literal(token::Lit { kind, symbol, .. }) => (kind, symbol),
+                _ => return,
+            };
+            // NOTE: The symbol keeps the literal as written, so the escapes of regular strings are not processed,
+            // while raw strings (e.g. `r#"cargo:rustc-cfg=foo"#`) have none.
+            let contents = symbol.as_str();
+            let lines = match kind {
+                token::LitKind::Str => contents.split("\\n").collect::<Vec<_>>(),
+                token::LitKind::StrRaw(_) => contents.lines().collect(),
+                _ => return,
+            };
+            for line in lines {
+                for feature in directive_features(line) {
+                    self.stab_ctx.record_cargo_feature(feature, tok.span);
+                }
+            }
+        }
+    }
+
+    fn gate(&self, attrs: &[ast::Attribute]) -> Option<Gate> {
+        attrs.iter().find_map(|attr| self.rustversion_gate(attr).or_else(|| self.probe_cfg_gate(attr)))
+    }
+
+    // NOTE: `rustversion` attributes are proc macros, so they must be caught before expansion.
+    fn rustversion_gate(&self, attr: &ast::Attribute) -> Option<Gate> {
+        let item = match &attr.kind {
+            ast::AttrKind::Normal(item) => item,
+            _ => return None,
+        };
+        let segments = &item.path.segments;
+        // `rustversion::attr` applies other attributes conditionally, the node itself is always compiled.
+        if segments.len() != 2
+            || &*segments[0].ident.as_str() != "rustversion"
+            || &*segments[1].ident.as_str() == "attr"
+        {
+            return None;
+        }
+
+        let snippet = self.session.source_map().span_to_snippet(attr.span).ok()?;
+        let condition = snippet.trim_start_matches("#[").trim_end_matches(']').to_string();
+        let expr = condition.chars().filter(|c| !c.is_whitespace()).skip("rustversion::".len()).collect::<String>();
+        // Only conditions with a lower bound keep code from older compilers, the others (e.g. `before` or `nightly`)
+        // still let it be compiled by the oldest ones.
+        let version = rustversion_min_version(&expr)?;
+        Some(Gate { version, condition })
+    }
+
+    fn probe_cfg_gate(&self, attr: &ast::Attribute) -> Option<Gate> {
+        if !attr.has_name(sym::cfg) {
+            return None;
+        }
+        let cfg = match &attr.meta_item_list()?[..] {
+            [nested] => nested.meta_item()?.clone(),
+            _ => return None,
+        };
+
+        let version = self.probe_cfg_version(&cfg)?;
+        let predicate = self.session.source_map().span_to_snippet(cfg.span).unwrap_or_default();
+        Some(Gate { condition: format!("cfg({})", predicate), version })
+    }
+
+    // Only predicates that can't hold unless the probes succeed gate the code, i.e. `not` and `any` are ignored.
+    fn probe_cfg_version(&self, cfg: &ast::MetaItem) -> Option<Version> {
+        match &cfg.kind {
+            ast::MetaItemKind::Word => self.probe_cfgs.get(&*cfg.name_or_empty().as_str()).cloned(),
+            ast::MetaItemKind::List(items) if cfg.name_or_empty() == sym::all => {
+                items.iter().filter_map(|i| i.meta_item()).filter_map(|i| self.probe_cfg_version(i)).max()
+            },
+            _ => None,
+        }
+    }
+
+    fn inactive_cfg(&self, attrs: &[ast::Attribute]) -> Option<String> {
+        attrs
+            .iter()
+            .filter(|a| a.has_name(sym::cfg))
+            .filter_map(|a| a.meta_item_list())
+            .filter_map(|list| match &list[..] {
+                [nested] => nested.meta_item().cloned(),
+                _ => None,
+            })
+            .find(|cfg| !rustc_attr::cfg_matches(cfg, &self.session.parse_sess, None))
+            .map(|cfg| self.session.source_map().span_to_snippet(cfg.span).unwrap_or_default())
+    }
+
+    // Nodes disabled by a `cfg` will be removed during expansion, so the syntactic checks that would run after it
+    // are run here instead. Their results are kept apart, as they don't apply to the analyzed configuration.
+    // NOTE: Out-of-line modules are loaded during expansion, so the contents of disabled ones can't be checked.
+    fn walk_with_cfg<P, W>(&mut self, attrs: &[ast::Attribute], span: Span, process: P, walk: W)
+    where
+        P: FnOnce(&mut post_expansion::Visitor<'_, 'scx, '_>),
+        W: FnOnce(&mut Self),
+    {
+        if let Some(gate) = self.gate(attrs) {
+            self.gates.push((context::convert_span(self.session.source_map(), span), gate));
+        }
+
+        let in_inactive_cfg = self.in_inactive_cfg;
+        if !in_inactive_cfg {
+            if let Some(cfg) = self.inactive_cfg(attrs) {
+                let mut stab_ctx = StabCtxt::new(self.session);
+                process(&mut post_expansion::Visitor::new(&mut stab_ctx, None, self.session.source_map()));
+                self.inactive.push((cfg, stab_ctx));
+                self.in_inactive_cfg = true;
+            }
+        }
+
+        walk(self);
+        self.in_inactive_cfg = in_inactive_cfg;
     }
 
     fn walk_cfg_metas(&mut self, item: &ast::MetaItem) {
@@ -66,10 +231,12 @@ impl<'ast> visit::Visitor<'ast> for Visitor<'_, '_> {
     }
 
     fn visit_mac(&mut self, mac: &ast::MacCall) {
+        self.check_build_script_directive(mac);
+
         let segments = &mac.path.segments;
         if segments.len() == 1 && segments[0].ident.name == sym::cfg {
             let tts = mac.args.inner_tokens();
-            let mut parser = rustc_parse::stream_to_parser(self.parse_sess, tts, MACRO_ARGUMENTS);
+            let mut parser = rustc_parse::stream_to_parser(&self.session.parse_sess, tts, MACRO_ARGUMENTS);
             if let Ok(cfg) = parser.parse_meta_item() {
                 self.walk_cfg_metas(&cfg);
             }
@@ -78,6 +245,54 @@ impl<'ast> visit::Visitor<'ast> for Visitor<'_, '_> {
         visit::walk_mac(self, mac);
     }
 
+    fn visit_item(&mut self, item: &ast::Item) {
+        self.walk_with_cfg(&item.attrs, item.span, |v| v.visit_item(item), |v| visit::walk_item(v, item));
+    }
+
+    fn visit_assoc_item(&mut self, item: &ast::AssocItem, ctxt: AssocCtxt) {
+        self.walk_with_cfg(
+            &item.attrs,
+            item.span,
+            |v| v.visit_assoc_item(item, ctxt),
+            |v| visit::walk_assoc_item(v, item, ctxt),
+        );
+    }
+
+    fn visit_foreign_item(&mut self, item: &ast::ForeignItem) {
+        self.walk_with_cfg(
+            &item.attrs,
+            item.span,
+            |v| v.visit_foreign_item(item),
+            |v| visit::walk_foreign_item(v, item),
+        );
+    }
+
+    fn visit_stmt(&mut self, stmt: &ast::Stmt) {
+        self.walk_with_cfg(stmt.attrs(), stmt.span, |v| v.visit_stmt(stmt), |v| visit::walk_stmt(v, stmt));
+    }
+
+    fn visit_arm(&mut self, arm: &ast::Arm) {
+        self.walk_with_cfg(&arm.attrs, arm.span, |v| v.visit_arm(arm), |v| visit::walk_arm(v, arm));
+    }
+
+    fn visit_variant(&mut self, variant: &ast::Variant) {
+        self.walk_with_cfg(
+            &variant.attrs,
+            variant.span,
+            |v| v.visit_variant(variant),
+            |v| visit::walk_variant(v, variant),
+        );
+    }
+
+    fn visit_struct_field(&mut self, field: &ast::StructField) {
+        self.walk_with_cfg(
+            &field.attrs,
+            field.span,
+            |v| v.visit_struct_field(field),
+            |v| visit::walk_struct_field(v, field),
+        );
+    }
+
     fn visit_param(&mut self, param: &ast::Param) {
         if !param.attrs.is_empty() {
             self.stab_ctx.record_lang_feature(sym::param_attrs, param.span);
@@ -121,8 +336,48 @@ impl<'ast> visit::Visitor<'ast> for Visitor<'_, '_> {
 
 pub fn process_crate(wrapper: &mut Wrapper, session: &Session, krate: &ast::Crate) {
     let mut stab_ctx = StabCtxt::new(session);
-    let mut visitor = Visitor::new(&mut stab_ctx, &session.parse_sess);
+    let build_script = wrapper.crate_name.starts_with("build_script_");
+    let mut visitor = Visitor::new(&mut stab_ctx, session, &wrapper.probe_cfgs, build_script);
     visit::walk_crate(&mut visitor, &krate);
+    let inactive = mem::take(&mut visitor.inactive);
+    let gates = mem::take(&mut visitor.gates);
 
     stab_ctx.dump(wrapper);
+    for (cfg, inactive_ctx) in inactive {
+        inactive_ctx.dump_inactive(wrapper, &cfg);
+    }
+    wrapper.gates.extend(gates);
+}
+
+// Returns the oldest compiler that satisfies a `rustversion` condition (without whitespace), if it implies one.
+fn rustversion_min_version(expr: &str) -> Option<Version> {
+    let (name, args) = match expr.find('(') {
+        Some(pos) if expr.ends_with(')') => (&expr[..pos], &expr[pos + 1..expr.len() - 1]),
+        _ => (expr, ""),
+    };
+
+    match name {
+        // NOTE: Dates (e.g. `since(2019-01-01)`) refer to nightly compilers and don't parse as versions.
+        "since" | "stable" if !args.is_empty() => parse_rust_version(args),
+        "all" => split_args(args).into_iter().filter_map(rustversion_min_version).max(),
+        _ => None,
+    }
+}
+
+fn split_args(args: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (pos, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&args[start..pos]);
+                start = pos + 1;
+            },
+            _ => {},
+        }
+    }
+    parts.push(&args[start..]);
+    parts
 }
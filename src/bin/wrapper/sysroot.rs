@@ -0,0 +1,123 @@
+use std::ffi::OsString;
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+use std::process::Command;
+use std::{env, str};
+
+use anyhow::{bail, Result};
+
+/// The place the sysroot was taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Arg,
+    Env,
+    Rustup,
+    Wrapper,
+    Rustc,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Source::Arg => "--sysroot argument",
+            Source::Env => "SYSROOT environment variable",
+            Source::Rustup => "RUSTUP_HOME and RUSTUP_TOOLCHAIN environment variables",
+            Source::Wrapper => "toolchain used to build the wrapper",
+            Source::Rustc => "rustc --print sysroot",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+// The lookup order is the same used by clippy: an explicit argument, then the environment, then rustup,
+// and finally the toolchain the wrapper was built with. Asking rustc is only a last resort, as the rustc
+// found in PATH may belong to a different toolchain (e.g. because of a rust-toolchain file in the project).
+pub fn find(args: &[String]) -> Result<(PathBuf, Source)> {
+    if let Some(sysroot) = arg_value(args, "--sysroot") {
+        return Ok((sysroot.into(), Source::Arg));
+    }
+
+    if let Some(sysroot) = non_empty(env::var_os("SYSROOT")) {
+        return Ok((sysroot.into(), Source::Env));
+    }
+
+    if let Some(sysroot) = toolchain_path(env::var_os("RUSTUP_HOME"), env::var_os("RUSTUP_TOOLCHAIN")) {
+        return Ok((sysroot, Source::Rustup));
+    }
+
+    let own_sysroot = option_env!("SYSROOT").map(PathBuf::from).or_else(|| {
+        toolchain_path(option_env!("RUSTUP_HOME").map(Into::into), option_env!("RUSTUP_TOOLCHAIN").map(Into::into))
+    });
+    if let Some(sysroot) = own_sysroot {
+        return Ok((sysroot, Source::Wrapper));
+    }
+
+    let output = Command::new("rustc").args(vec!["--print", "sysroot"]).output()?;
+    if !output.status.success() {
+        bail!("rustc returned error exit status");
+    }
+    let sysroot = str::from_utf8(&output.stdout)?;
+    Ok((sysroot.trim_end().into(), Source::Rustc))
+}
+
+fn arg_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next().map(String::as_str);
+        }
+        if arg.starts_with(name) && arg[name.len()..].starts_with('=') {
+            return Some(&arg[name.len() + 1..]);
+        }
+    }
+    None
+}
+
+fn toolchain_path(home: Option<OsString>, toolchain: Option<OsString>) -> Option<PathBuf> {
+    let mut path = PathBuf::from(non_empty(home)?);
+    path.push("toolchains");
+    path.push(non_empty(toolchain)?);
+    if path.is_dir() { Some(path) } else { None }
+}
+
+fn non_empty(value: Option<OsString>) -> Option<OsString> {
+    value.filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn arg_values() {
+        assert_eq!(Some("/x"), arg_value(&args(&["rustc", "--sysroot=/x", "lib.rs"]), "--sysroot"));
+        assert_eq!(Some("/x"), arg_value(&args(&["rustc", "--sysroot", "/x", "lib.rs"]), "--sysroot"));
+        assert_eq!(None, arg_value(&args(&["rustc", "--sysroot"]), "--sysroot"));
+        assert_eq!(None, arg_value(&args(&["rustc", "--sysrootx=/x", "lib.rs"]), "--sysroot"));
+        assert_eq!(None, arg_value(&args(&["rustc", "lib.rs"]), "--sysroot"));
+    }
+
+    #[test]
+    fn sysroot_arg() {
+        let (sysroot, source) = find(&args(&["rustc", "--sysroot=/x", "lib.rs"])).unwrap();
+        assert_eq!((PathBuf::from("/x"), Source::Arg), (sysroot, source));
+        let (sysroot, source) = find(&args(&["rustc", "--sysroot", "/x", "lib.rs"])).unwrap();
+        assert_eq!((PathBuf::from("/x"), Source::Arg), (sysroot, source));
+    }
+
+    #[test]
+    fn toolchain_paths() {
+        let home = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(home.path().join("toolchains/nightly")).unwrap();
+
+        let path = toolchain_path(Some(home.path().into()), Some("nightly".into()));
+        assert_eq!(Some(home.path().join("toolchains/nightly")), path);
+        assert_eq!(None, toolchain_path(Some(home.path().into()), Some("stable".into())));
+        assert_eq!(None, toolchain_path(Some(home.path().into()), Some("".into())));
+        assert_eq!(None, toolchain_path(None, Some("nightly".into())));
+    }
+}
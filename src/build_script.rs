@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::feature::{CargoFileAnalysis, Feature, FeatureKind};
+
+/// Build script directives that need a minimum version of cargo, along with that version.
+// NOTE: Versions come from the Cargo section of the Rust release notes, or from the cargo changelog for
+// the ones since 1.48. Older versions of cargo take unknown directives as metadata, so they are ignored.
+const DIRECTIVES: &[(&str, &str)] = &[
+    // Release notes of 1.19.0: build scripts can ask to be rerun when an environment variable changes.
+    ("rerun-if-env-changed", "1.19.0"),
+    // Release notes of 1.19.0: "Build scripts can now add environment variables to the environment the crate
+    // is being compiled in".
+    ("rustc-env", "1.19.0"),
+    // Changelog of 1.56: "Build scripts can now pass additional linker arguments for binaries or all linkable
+    // targets". They were nightly-only (`-Zextra-link-arg`) since 1.50.
+    ("rustc-link-arg", "1.56.0"),
+    ("rustc-link-arg-bins", "1.56.0"),
+    ("rustc-link-arg-bin", "1.56.0"),
+    // NOTE: `rustc-link-arg-cdylib` is the new name of `rustc-cdylib-link-arg`, which needs no newer cargo.
+    ("rustc-link-arg-cdylib", "1.56.0"),
+    // Changelog of 1.60: the linker arguments for tests, examples and benchmarks.
+    ("rustc-link-arg-tests", "1.60.0"),
+    ("rustc-link-arg-examples", "1.60.0"),
+    ("rustc-link-arg-benches", "1.60.0"),
+    // Build scripts chapter of the cargo book: "MSRV: Respected as of 1.80".
+    ("rustc-check-cfg", "1.80.0"),
+];
+
+/// Returns the cargo features needed by a line printed by a build script, e.g. `cargo:rustc-env=FOO=bar`.
+pub fn directive_features(line: &str) -> Vec<Feature> {
+    let mut features = Vec::new();
+    let directive = if line.starts_with("cargo::") {
+        // Build scripts chapter of the cargo book: "MSRV: 1.77 is required for `cargo::KEY=VALUE` syntax".
+        features.push(cargo_feature("build_script_double_colon", "1.77.0"));
+        &line["cargo::".len()..]
+    } else if line.starts_with("cargo:") {
+        &line["cargo:".len()..]
+    } else {
+        return features;
+    };
+
+    let key = directive.split('=').next().unwrap_or_default();
+    if let Some((key, since)) = DIRECTIVES.iter().find(|(k, _)| *k == key) {
+        features.push(cargo_feature(&format!("build_script_{}", key.replace('-', "_")), since));
+    }
+    features
+}
+
+/// Finds the cargo features needed by the output of a build script, as saved by cargo after running it.
+pub fn analyze_output(path: &Path) -> Result<CargoFileAnalysis> {
+    let contents = fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+
+    let mut analysis = CargoFileAnalysis { path: path.into(), ..Default::default() };
+    for (index, line) in contents.lines().enumerate() {
+        for feature in directive_features(line) {
+            analysis.add_line_use(feature, index + 1, line.len());
+        }
+    }
+    Ok(analysis)
+}
+
+fn cargo_feature(name: &str, since: &str) -> Feature {
+    Feature { name: name.into(), kind: FeatureKind::Cargo, since: since.parse().ok() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(line: &str) -> Vec<(String, String)> {
+        directive_features(line).into_iter().map(|f| (f.name, f.since.unwrap().to_string())).collect()
+    }
+
+    #[test]
+    fn single_colon_directives() {
+        assert_eq!(
+            vec![("build_script_rustc_env".to_string(), "1.19.0".to_string())],
+            names("cargo:rustc-env=FOO=bar")
+        );
+        assert_eq!(
+            vec![("build_script_rustc_link_arg_bin".to_string(), "1.56.0".to_string())],
+            names("cargo:rustc-link-arg-bin=foo=-Wl,--foo")
+        );
+    }
+
+    #[test]
+    fn double_colon_directives() {
+        let expected = vec![
+            ("build_script_double_colon".to_string(), "1.77.0".to_string()),
+            ("build_script_rustc_check_cfg".to_string(), "1.80.0".to_string()),
+        ];
+        assert_eq!(expected, names("cargo::rustc-check-cfg=cfg(foo)"));
+
+        // The syntax alone needs a newer cargo, even for directives that are older than it.
+        assert_eq!(
+            vec![("build_script_double_colon".to_string(), "1.77.0".to_string())],
+            names("cargo::rustc-cfg=foo")
+        );
+    }
+
+    #[test]
+    fn other_lines() {
+        assert!(names("cargo:rustc-cfg=foo").is_empty());
+        // Prefixes of known directives are different directives (or metadata).
+        assert!(names("cargo:rustc-link-arg-binaries=foo").is_empty());
+        assert!(names("rustc-env=FOO=bar").is_empty());
+        assert!(names("warning: cargo:rustc-env=FOO=bar").is_empty());
+        assert!(names("").is_empty());
+    }
+}
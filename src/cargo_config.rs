@@ -0,0 +1,234 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use anyhow::{Context, Result};
+
+use crate::feature::CargoFileAnalysis;
+use crate::manifest;
+use crate::toml_spans::TomlSpans;
+
+const CONFIG_DIR: &str = ".cargo";
+const CONFIG_NAMES: [&str; 2] = ["config", "config.toml"];
+
+/// A configuration file that applies to a cargo invocation.
+#[derive(Debug, Clone)]
+pub struct ConfigFile {
+    pub path: PathBuf,
+    pub contents: String,
+    pub value: toml::Value,
+}
+
+impl ConfigFile {
+    pub fn read(path: PathBuf) -> Result<Self> {
+        let contents = fs::read_to_string(&path).with_context(|| format!("could not read {}", path.display()))?;
+        let value = contents.parse().with_context(|| format!("could not parse {}", path.display()))?;
+        Ok(Self { path, contents, value })
+    }
+
+    /// Returns the value found under the given dotted key (e.g. `build.rustc-wrapper`), if any.
+    pub fn get(&self, key: &str) -> Option<&toml::Value> {
+        key.split('.').try_fold(&self.value, |value, part| value.get(part))
+    }
+
+    /// Resolves a path-like value the same way cargo does: values containing a path separator
+    /// are relative to the directory that contains the `.cargo` directory, others are looked up in PATH.
+    pub fn resolve_program(&self, value: &str) -> PathBuf {
+        if value.contains('/') || value.contains('\\') {
+            let root = self.path.parent().and_then(Path::parent).unwrap_or_else(|| Path::new(""));
+            root.join(value)
+        } else {
+            PathBuf::from(value)
+        }
+    }
+}
+
+/// Finds the configuration files cargo would read when run from `cwd`, from highest to lowest precedence.
+///
+/// This mimics cargo's hierarchical lookup: every ancestor of `cwd` is searched for a `.cargo` directory,
+/// and the one in `CARGO_HOME` is read last if it was not found on the way.
+pub fn discover(cwd: &Path) -> Result<Vec<ConfigFile>> {
+    discover_paths(cwd).into_iter().map(ConfigFile::read).collect()
+}
+
+/// Finds the paths of the configuration files cargo would read when run from `cwd`, without reading them
+/// (see [`discover`](fn.discover.html)).
+pub fn discover_paths(cwd: &Path) -> Vec<PathBuf> {
+    config_paths(cwd, cargo_home())
+}
+
+fn config_paths(cwd: &Path, cargo_home: Option<PathBuf>) -> Vec<PathBuf> {
+    let mut dirs = cwd.ancestors().map(|dir| dir.join(CONFIG_DIR)).collect::<Vec<_>>();
+    if let Some(home) = cargo_home {
+        if !dirs.contains(&home) {
+            dirs.push(home);
+        }
+    }
+
+    // NOTE: When both files exist cargo uses the one without extension.
+    dirs.iter().filter_map(|dir| CONFIG_NAMES.iter().map(|name| dir.join(name)).find(|path| path.is_file())).collect()
+}
+
+/// Returns the compiler wrapper cargo would use when run from `cwd`, if any.
+pub fn rustc_wrapper(cwd: &Path) -> Result<Option<PathBuf>> {
+    if let Some(wrapper) = env_rustc_wrapper(|var| env::var_os(var)) {
+        return Ok(Some(wrapper));
+    }
+
+    let files = discover(cwd).context("could not read cargo configuration")?;
+    Ok(config_rustc_wrapper(&files))
+}
+
+// The environment takes precedence over the configuration files.
+fn env_rustc_wrapper(var_os: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    ["RUSTC_WRAPPER", "CARGO_BUILD_RUSTC_WRAPPER"]
+        .iter()
+        .filter_map(|var| var_os(var))
+        .find(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+// The files are expected from highest to lowest precedence, as returned by `discover`.
+fn config_rustc_wrapper(files: &[ConfigFile]) -> Option<PathBuf> {
+    files.iter().find_map(|file| match file.get("build.rustc-wrapper") {
+        Some(toml::Value::String(value)) if !value.is_empty() => Some(file.resolve_program(value)),
+        _ => None,
+    })
+}
+
+/// Finds the configuration features that need a minimum version of cargo.
+pub fn analyze(file: &ConfigFile) -> CargoFileAnalysis {
+    let mut analysis = CargoFileAnalysis { path: file.path.clone(), ..Default::default() };
+    let spans = TomlSpans::new(&file.path.display().to_string(), &file.contents);
+
+    // The name of the file is what needs the newer cargo, so there's no span to point at.
+    if file.path.extension().map_or(false, |ext| ext == "toml") {
+        manifest::record_use(&mut analysis, &spans, "config_toml", "1.39.0", &[]);
+    }
+    if file.get("env").is_some() {
+        manifest::record_use(&mut analysis, &spans, "config_env", "1.56.0", &["env"]);
+    }
+
+    let targets = file.get("target").and_then(toml::Value::as_table).into_iter().flat_map(|t| t.keys());
+    for target in targets.filter(|t| t.starts_with("cfg(")) {
+        manifest::record_use(&mut analysis, &spans, "config_target_cfg", "1.39.0", &["target", target]);
+    }
+    analysis
+}
+
+fn cargo_home() -> Option<PathBuf> {
+    env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(CONFIG_DIR)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_file(path: &str, contents: &str) -> ConfigFile {
+        ConfigFile { path: path.into(), contents: contents.into(), value: contents.parse().unwrap() }
+    }
+
+    fn write(path: &Path, contents: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn config_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        write(&root.join("ws/.cargo/config.toml"), "");
+        write(&root.join("ws/member/.cargo/config"), "");
+        write(&root.join("ws/member/.cargo/config.toml"), "");
+        write(&root.join("home/config.toml"), "");
+
+        let cwd = root.join("ws/member/src");
+        let expected = vec![
+            root.join("ws/member/.cargo/config"),
+            root.join("ws/.cargo/config.toml"),
+            root.join("home/config.toml"),
+        ];
+        assert_eq!(expected, config_paths(&cwd, Some(root.join("home"))));
+
+        // The configuration in the home directory is only read once.
+        let expected = vec![root.join("ws/member/.cargo/config"), root.join("ws/.cargo/config.toml")];
+        assert_eq!(expected, config_paths(&cwd, Some(root.join("ws/.cargo"))));
+    }
+
+    #[test]
+    fn paths_are_not_read() {
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("ws/.cargo/config"), "[build");
+
+        let paths = config_paths(&dir.path().join("ws"), None);
+        assert_eq!(vec![dir.path().join("ws/.cargo/config")], paths);
+        assert!(ConfigFile::read(paths[0].clone()).is_err());
+    }
+
+    #[test]
+    fn env_wrapper() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |var: &str| vars.iter().find(|(name, _)| *name == var).map(|(_, value)| OsString::from(value))
+        };
+        assert_eq!(None, env_rustc_wrapper(env(&[])));
+        assert_eq!(Some("sccache".into()), env_rustc_wrapper(env(&[("CARGO_BUILD_RUSTC_WRAPPER", "sccache")])));
+        assert_eq!(
+            Some("cachepot".into()),
+            env_rustc_wrapper(env(&[("RUSTC_WRAPPER", "cachepot"), ("CARGO_BUILD_RUSTC_WRAPPER", "sccache")]))
+        );
+        // An empty variable disables the wrapper of that variable only.
+        assert_eq!(
+            Some("sccache".into()),
+            env_rustc_wrapper(env(&[("RUSTC_WRAPPER", ""), ("CARGO_BUILD_RUSTC_WRAPPER", "sccache")]))
+        );
+    }
+
+    #[test]
+    fn config_wrapper() {
+        let member = config_file("/ws/member/.cargo/config", "[build]\njobs = 2\n");
+        let workspace = config_file("/ws/.cargo/config.toml", "[build]\nrustc-wrapper = \"tools/wrapper\"\n");
+        let home = config_file("/home/.cargo/config.toml", "[build]\nrustc-wrapper = \"sccache\"\n");
+
+        assert_eq!(None, config_rustc_wrapper(&[]));
+        assert_eq!(None, config_rustc_wrapper(&[member.clone()]));
+        assert_eq!(Some("sccache".into()), config_rustc_wrapper(&[home.clone()]));
+        // Paths are relative to the directory that contains `.cargo`, and the closest file wins.
+        assert_eq!(Some("/ws/tools/wrapper".into()), config_rustc_wrapper(&[member, workspace, home]));
+    }
+
+    #[test]
+    fn config_features() {
+        let contents = "[env]\nFOO = \"bar\"\n\n[target.'cfg(unix)']\nrunner = \"foo\"\n\n[target.x86_64-unknown-linux-gnu]\nlinker = \"cc\"\n";
+        let analysis = analyze(&config_file(".cargo/config.toml", contents));
+
+        let mut features = analysis
+            .features
+            .iter()
+            .map(|f| (f.name.as_str(), f.since.as_ref().unwrap().to_string()))
+            .collect::<Vec<_>>();
+        features.sort();
+        assert_eq!(
+            vec![
+                ("config_env", "1.56.0".into()),
+                ("config_target_cfg", "1.39.0".into()),
+                ("config_toml", "1.39.0".into())
+            ],
+            features
+        );
+
+        let span =
+            |name: &str| analysis.uses.get(name).map(|uses| uses.iter().map(ToString::to_string).collect::<Vec<_>>());
+        assert_eq!(Some(vec![".cargo/config.toml 1:1 1:4".to_string()]), span("config_env"));
+        assert_eq!(Some(vec![".cargo/config.toml 4:8 4:19".to_string()]), span("config_target_cfg"));
+        // The name of the file needs the newer cargo, not any of its entries.
+        assert_eq!(None, span("config_toml"));
+    }
+
+    #[test]
+    fn no_config_features() {
+        let analysis = analyze(&config_file(".cargo/config", "[build]\njobs = 2\n"));
+        assert!(analysis.features.is_empty());
+    }
+}
@@ -0,0 +1,33 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::manifest;
+
+/// Configuration of the tool, taken from the `package.metadata.minver` (or `workspace.metadata.minver`)
+/// table of the manifest being analyzed.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Target triples to analyze. The host is analyzed if empty.
+    pub targets: Vec<String>,
+    /// Cfgs set by the build script after probing the compiler (e.g. with `version_check` or `autocfg`),
+    /// along with the version of the first compiler that makes the probe succeed.
+    pub probe_cfgs: BTreeMap<String, String>,
+}
+
+impl Config {
+    pub fn from_manifest(path: &Path) -> Result<Self> {
+        let manifest = manifest::read(path)?;
+
+        let table = ["package", "workspace"]
+            .iter()
+            .find_map(|key| manifest.get(key).and_then(|t| t.get("metadata")).and_then(|t| t.get("minver")));
+        match table {
+            Some(table) => table.clone().try_into().context("invalid minver configuration"),
+            None => Ok(Default::default()),
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt::{self, Display};
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// Accepted lang features that are detected as the lib feature that implements them.
+const LIB_FEATURES: &[(&str, &str)] =
+    &[("compile_error", "compile_error_macro"), ("global_allocator", "global_allocator")];
+
+/// Accepted lang features that can't be detected, along with the reason why.
+const UNDETECTABLE: &[(&str, &str)] = &[("match_beginning_vert", "there is no trace of it after parsing")];
+
+/// An accepted lang feature of the pinned toolchain, as reported by the wrapper.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptedFeature {
+    pub name: String,
+    pub since: Version,
+    /// The passes of the wrapper that detect the feature.
+    pub passes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "class", rename_all = "kebab-case")]
+pub enum Detection {
+    Detected { passes: Vec<String> },
+    Lib { feature: String },
+    Undetectable { reason: String },
+    NotImplemented,
+}
+
+impl Display for Detection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Detection::Detected { passes } => write!(f, "detected ({})", passes.join(", ")),
+            Detection::Lib { feature } => write!(f, "detected as lib feature {}", feature),
+            Detection::Undetectable { reason } => write!(f, "undetectable ({})", reason),
+            Detection::NotImplemented => write!(f, "not implemented"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureCoverage {
+    pub name: String,
+    pub since: Version,
+    pub detection: Detection,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CoverageReport {
+    pub features: Vec<FeatureCoverage>,
+    /// The oldest version that stabilized a feature which is not detected.
+    pub blind_spots_since: Option<Version>,
+    pub warning: Option<String>,
+}
+
+impl CoverageReport {
+    pub fn new(accepted: Vec<AcceptedFeature>) -> Self {
+        let mut features = accepted.into_iter().map(classify).collect::<Vec<_>>();
+        features.sort_by(|a, b| a.since.cmp(&b.since).then_with(|| a.name.cmp(&b.name)));
+
+        // Features accepted in 1.0.0 were part of the language from the start, so they can't raise the minimum version.
+        let first_release = Version::new(1, 0, 0);
+        let blind_spots_since = features
+            .iter()
+            .filter(|f| f.since > first_release)
+            .filter(|f| matches!(f.detection, Detection::Undetectable { .. } | Detection::NotImplemented))
+            .map(|f| f.since.clone())
+            .min();
+        let warning = blind_spots_since.as_ref().map(|version| {
+            format!("blind spots above version {}: minimum versions from {} on may be underestimated", version, version)
+        });
+
+        Self { features, blind_spots_since, warning }
+    }
+}
+
+fn classify(feature: AcceptedFeature) -> FeatureCoverage {
+    let detection = if !feature.passes.is_empty() {
+        Detection::Detected { passes: feature.passes }
+    } else if let Some((_, lib_feature)) = LIB_FEATURES.iter().find(|(name, _)| *name == feature.name) {
+        Detection::Lib { feature: lib_feature.to_string() }
+    } else if let Some((_, reason)) = UNDETECTABLE.iter().find(|(name, _)| *name == feature.name) {
+        Detection::Undetectable { reason: reason.to_string() }
+    } else {
+        Detection::NotImplemented
+    };
+
+    FeatureCoverage { name: feature.name, since: feature.since, detection }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coverage_floor_skips_first_release() {
+        let feature = |name: &str, since: &str| AcceptedFeature {
+            name: name.into(),
+            since: since.parse().unwrap(),
+            passes: Vec::new(),
+        };
+        let report = CoverageReport::new(vec![feature("associated_types", "1.0.0"), feature("foo", "1.20.0")]);
+
+        assert_eq!(Some("1.20.0".parse().unwrap()), report.blind_spots_since, "expected blind spot floor to match");
+    }
+}
@@ -1,14 +1,22 @@
-use std::path::PathBuf;
-use std::process::Command;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::{env, str};
 
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use structopt::StructOpt;
 
-use crate::feature::Analysis;
+use crate::config::Config;
+use crate::coverage::CoverageReport;
+use crate::feature::{self, Analysis};
 use crate::ipc::Server;
-use crate::SERVER_PORT_ENV;
+use crate::manifest;
+use crate::powerset::{self, describe_feature_set, FeatureSetAnalysis};
+use crate::{build_script, cargo_config};
+use crate::{BEST_EFFORT_ENV, COVERAGE_ARG, FAST_ENV, PROBE_CFGS_ENV, RUSTC_WRAPPER_ENV, SERVER_PORT_ENV, VERBOSE_ENV};
 
+const CARGO_ENV: &str = "CARGO";
+const TARGET_DIR_ENV: &str = "CARGO_TARGET_DIR";
 const WRAPPER_ENV: &str = "RUSTC_WRAPPER";
 const WRAPPER_NAME: &str = "minver-wrapper";
 
@@ -23,6 +31,9 @@ pub struct Options {
     /// Don't print progress output.
     #[structopt(short = "q", long)]
     quiet: bool,
+    /// Use verbose output.
+    #[structopt(short = "v", long)]
+    verbose: bool,
     /// Space-separated list of cargo features to activate
     #[structopt(long)]
     features: Option<String>,
@@ -32,15 +43,87 @@ pub struct Options {
     /// Do not activate the `default` cargo feature
     #[structopt(long)]
     no_default_features: bool,
-    /// Check all the tests.
+    /// Analyze the package with no cargo features and with each feature on its own.
+    #[structopt(long, conflicts_with_all = &["features", "all-features", "no-default-features", "feature-powerset"])]
+    each_feature: bool,
+    /// Analyze the package with every combination of its cargo features.
+    #[structopt(long, conflicts_with_all = &["features", "all-features", "no-default-features"])]
+    feature_powerset: bool,
+    /// Maximum number of cargo features in a combination of --feature-powerset.
+    #[structopt(long, value_name = "N", requires = "feature-powerset")]
+    depth: Option<usize>,
+    /// Package to check (can be specified multiple times).
+    #[structopt(short = "p", long = "package", value_name = "SPEC", number_of_values = 1)]
+    packages: Vec<String>,
+    /// Check all packages in the workspace.
+    #[structopt(long, alias = "all")]
+    workspace: bool,
+    /// Exclude packages from the check (can be specified multiple times).
+    #[structopt(long, value_name = "SPEC", number_of_values = 1)]
+    exclude: Vec<String>,
+    /// Check only this package's library.
     #[structopt(long)]
-    tests: bool,
+    lib: bool,
+    /// Check only the specified binary (can be specified multiple times).
+    #[structopt(long, value_name = "NAME", number_of_values = 1)]
+    bin: Vec<String>,
+    /// Check all the binaries.
+    #[structopt(long)]
+    bins: bool,
+    /// Check only the specified example (can be specified multiple times).
+    #[structopt(long, value_name = "NAME", number_of_values = 1)]
+    example: Vec<String>,
     /// Check all the examples.
     #[structopt(long)]
     examples: bool,
+    /// Check only the specified test target (can be specified multiple times).
+    #[structopt(long, value_name = "NAME", number_of_values = 1)]
+    test: Vec<String>,
+    /// Check all the tests.
+    #[structopt(long)]
+    tests: bool,
+    /// Check only the specified bench target (can be specified multiple times).
+    #[structopt(long, value_name = "NAME", number_of_values = 1)]
+    bench: Vec<String>,
     /// Check all the benches.
     #[structopt(long)]
     benches: bool,
+    /// Check all the targets.
+    #[structopt(long)]
+    all_targets: bool,
+    /// Check artifacts in release mode.
+    #[structopt(long)]
+    release: bool,
+    /// Check for the target triple (can be specified multiple times).
+    #[structopt(long = "target", value_name = "TRIPLE", number_of_values = 1)]
+    targets: Vec<String>,
+    /// Directory for all generated artifacts.
+    #[structopt(long, value_name = "DIRECTORY")]
+    target_dir: Option<PathBuf>,
+    /// Number of parallel jobs, defaults to # of CPUs.
+    #[structopt(short = "j", long, value_name = "N")]
+    jobs: Option<u32>,
+    /// Require Cargo.lock and cache are up to date.
+    #[structopt(long)]
+    frozen: bool,
+    /// Require Cargo.lock is up to date.
+    #[structopt(long)]
+    locked: bool,
+    /// Run without accessing the network.
+    #[structopt(long)]
+    offline: bool,
+    /// Unstable (nightly-only) flags to cargo (can be specified multiple times).
+    #[structopt(short = "Z", value_name = "FLAG", number_of_values = 1)]
+    unstable_flags: Vec<String>,
+    /// Keep analyzing crates that fail to compile, flagging their results as partial.
+    #[structopt(long)]
+    best_effort: bool,
+    /// Only run the syntactic checks, skipping the ones that need type information.
+    #[structopt(long)]
+    fast: bool,
+    /// Extra arguments passed verbatim to cargo check.
+    #[structopt(last = true)]
+    cargo_args: Vec<String>,
 }
 
 #[derive(Debug, Default)]
@@ -74,6 +157,11 @@ impl Driver {
         self
     }
 
+    pub fn verbose(&mut self, value: bool) -> &mut Self {
+        self.opts.verbose = value;
+        self
+    }
+
     pub fn features(&mut self, features: &str) -> &mut Self {
         self.opts.features = Some(features.into());
         self
@@ -89,8 +177,53 @@ impl Driver {
         self
     }
 
-    pub fn tests(&mut self, value: bool) -> &mut Self {
-        self.opts.tests = value;
+    pub fn each_feature(&mut self, value: bool) -> &mut Self {
+        self.opts.each_feature = value;
+        self
+    }
+
+    pub fn feature_powerset(&mut self, value: bool) -> &mut Self {
+        self.opts.feature_powerset = value;
+        self
+    }
+
+    pub fn depth(&mut self, depth: usize) -> &mut Self {
+        self.opts.depth = Some(depth);
+        self
+    }
+
+    pub fn package(&mut self, spec: &str) -> &mut Self {
+        self.opts.packages.push(spec.into());
+        self
+    }
+
+    pub fn workspace(&mut self, value: bool) -> &mut Self {
+        self.opts.workspace = value;
+        self
+    }
+
+    pub fn exclude(&mut self, spec: &str) -> &mut Self {
+        self.opts.exclude.push(spec.into());
+        self
+    }
+
+    pub fn lib(&mut self, value: bool) -> &mut Self {
+        self.opts.lib = value;
+        self
+    }
+
+    pub fn bin(&mut self, name: &str) -> &mut Self {
+        self.opts.bin.push(name.into());
+        self
+    }
+
+    pub fn bins(&mut self, value: bool) -> &mut Self {
+        self.opts.bins = value;
+        self
+    }
+
+    pub fn example(&mut self, name: &str) -> &mut Self {
+        self.opts.example.push(name.into());
         self
     }
 
@@ -99,76 +232,369 @@ impl Driver {
         self
     }
 
+    pub fn test(&mut self, name: &str) -> &mut Self {
+        self.opts.test.push(name.into());
+        self
+    }
+
+    pub fn tests(&mut self, value: bool) -> &mut Self {
+        self.opts.tests = value;
+        self
+    }
+
+    pub fn bench(&mut self, name: &str) -> &mut Self {
+        self.opts.bench.push(name.into());
+        self
+    }
+
     pub fn benches(&mut self, value: bool) -> &mut Self {
         self.opts.benches = value;
         self
     }
 
+    pub fn all_targets(&mut self, value: bool) -> &mut Self {
+        self.opts.all_targets = value;
+        self
+    }
+
+    pub fn release(&mut self, value: bool) -> &mut Self {
+        self.opts.release = value;
+        self
+    }
+
+    pub fn target(&mut self, triple: &str) -> &mut Self {
+        self.opts.targets.push(triple.into());
+        self
+    }
+
+    pub fn target_dir<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.opts.target_dir = Some(path.into());
+        self
+    }
+
+    pub fn jobs(&mut self, jobs: u32) -> &mut Self {
+        self.opts.jobs = Some(jobs);
+        self
+    }
+
+    pub fn frozen(&mut self, value: bool) -> &mut Self {
+        self.opts.frozen = value;
+        self
+    }
+
+    pub fn locked(&mut self, value: bool) -> &mut Self {
+        self.opts.locked = value;
+        self
+    }
+
+    pub fn offline(&mut self, value: bool) -> &mut Self {
+        self.opts.offline = value;
+        self
+    }
+
+    pub fn unstable_flag(&mut self, flag: &str) -> &mut Self {
+        self.opts.unstable_flags.push(flag.into());
+        self
+    }
+
+    pub fn best_effort(&mut self, value: bool) -> &mut Self {
+        self.opts.best_effort = value;
+        self
+    }
+
+    pub fn fast(&mut self, value: bool) -> &mut Self {
+        self.opts.fast = value;
+        self
+    }
+
+    pub fn cargo_arg(&mut self, arg: &str) -> &mut Self {
+        self.opts.cargo_args.push(arg.into());
+        self
+    }
+
+    /// Returns whether the package should be analyzed once per set of cargo features, with
+    /// [`execute_feature_sets`](#method.execute_feature_sets) instead of [`execute`](#method.execute).
+    pub fn checks_feature_sets(&self) -> bool {
+        self.opts.each_feature || self.opts.feature_powerset
+    }
+
     pub fn execute(&mut self) -> Result<Analysis> {
+        self.analyze_targets(None)
+    }
+
+    pub fn execute_feature_sets(&mut self) -> Result<Vec<FeatureSetAnalysis>> {
+        if !self.checks_feature_sets() {
+            bail!("neither --each-feature nor --feature-powerset was specified");
+        }
+        if self.opts.features.is_some() || self.opts.all_features || self.opts.no_default_features {
+            bail!("cargo features cannot be selected when analyzing sets of them");
+        }
+
+        let manifest_path = self.package_manifest_path().context("could not find the manifest of the package")?;
+        let features = manifest::package_features(&manifest_path).context("could not read cargo features")?;
+        let feature_sets = if self.opts.feature_powerset {
+            powerset::powerset(&features, self.opts.depth)
+        } else {
+            powerset::each_feature(&features)
+        };
+
+        let mut analyses = Vec::with_capacity(feature_sets.len());
+        for cargo_features in feature_sets {
+            let analysis = self
+                .analyze_targets(Some(&cargo_features))
+                .with_context(|| format!("failed to analyze features {}", describe_feature_set(&cargo_features)))?;
+            analyses.push(FeatureSetAnalysis { cargo_features, analysis });
+        }
+        Ok(analyses)
+    }
+
+    /// Reports which of the lang features accepted by the pinned toolchain are detected, and how.
+    pub fn coverage(&self) -> Result<CoverageReport> {
+        let wrapper_path = self.find_wrapper_path().context("could not find compiler wrapper")?;
+
+        // The wrapper is linked against the compiler libraries of the pinned toolchain.
+        let output = Command::new("rustup")
+            .arg("run")
+            .arg(env!("MINVER_TOOLCHAIN").trim_start_matches('+'))
+            .arg(wrapper_path)
+            .arg(COVERAGE_ARG)
+            .stderr(Stdio::inherit())
+            .output()
+            .context("failed to execute compiler wrapper")?;
+        if !output.status.success() {
+            bail!("compiler wrapper returned error exit status");
+        }
+
+        let accepted =
+            serde_json::from_slice(&output.stdout).context("invalid coverage report from compiler wrapper")?;
+        Ok(CoverageReport::new(accepted))
+    }
+
+    fn analyze_targets(&self, feature_set: Option<&[String]>) -> Result<Analysis> {
+        let targets = if self.opts.targets.is_empty() { self.config()?.targets } else { self.opts.targets.clone() };
+        if targets.is_empty() {
+            return self.analyze(None, feature_set);
+        }
+
+        // Cargo can only check a target at a time, so run the analysis for each of them and merge the results.
+        let mut analysis = Analysis::default();
+        for target in &targets {
+            let mut target_analysis = self
+                .analyze(Some(target), feature_set)
+                .with_context(|| format!("failed to analyze target {}", target))?;
+            target_analysis.tag_target(target);
+            analysis.merge(target_analysis);
+        }
+        Ok(analysis)
+    }
+
+    fn find_manifest_path(&self) -> Result<Option<PathBuf>> {
+        match &self.opts.manifest_path {
+            Some(path) => Ok(Some(path.clone())),
+            None => Ok(manifest::find(&env::current_dir()?)),
+        }
+    }
+
+    // The manifest of the package selected with `--package`, which may be any member of the workspace,
+    // or the one cargo would use otherwise.
+    fn package_manifest_path(&self) -> Result<PathBuf> {
+        let manifest_path = self.find_manifest_path()?.context("could not find Cargo.toml")?;
+        let spec = match &self.opts.packages[..] {
+            [] if !self.opts.workspace => return Ok(manifest_path),
+            [spec] => spec,
+            _ => bail!("cargo features can only be analyzed for one package at a time"),
+        };
+
+        let output = self.cargo_command("metadata", None).arg("--format-version=1").arg("--no-deps").output()?;
+        if !output.status.success() {
+            bail!("cargo metadata failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        let metadata = serde_json::from_slice::<serde_json::Value>(&output.stdout)?;
+
+        find_package_manifest(&metadata, spec)
+            .with_context(|| format!("package `{}` is not a member of the workspace", spec))
+    }
+
+    fn config(&self) -> Result<Config> {
+        match self.find_manifest_path()? {
+            Some(path) => Config::from_manifest(&path),
+            // Let cargo complain about the missing manifest.
+            None => Ok(Default::default()),
+        }
+    }
+
+    // Each set of cargo features gets its own target directory, so that runs don't clobber each other's artifacts.
+    fn feature_set_target_dir(&self, feature_set: &[String]) -> Result<PathBuf> {
+        let base = match (&self.opts.target_dir, env::var_os(TARGET_DIR_ENV)) {
+            (Some(path), _) => path.clone(),
+            (None, Some(path)) if !path.is_empty() => path.into(),
+            _ => {
+                let manifest_path = self.find_manifest_path()?.context("could not find Cargo.toml")?;
+                manifest_path.parent().unwrap_or_else(|| Path::new("")).join("target")
+            },
+        };
+        Ok(base.join("minver").join(powerset::feature_set_id(feature_set)))
+    }
+
+    fn analyze(&self, target: Option<&str>, feature_set: Option<&[String]>) -> Result<Analysis> {
+        let target_dir = match feature_set {
+            Some(feature_set) => Some(self.feature_set_target_dir(feature_set)?),
+            None => self.opts.target_dir.clone(),
+        };
+
         // Start a server that will receive the results of the analysis of each crate.
         let server = Server::new().context("could not start server")?;
 
         // Build the crate and its dependencies. Run cargo clean before to make sure we see all the code.
         // TODO: Store stability information to avoid unnecessary rebuilds.
-        self.cargo_clean().context("failed to execute cargo clean")?;
-        self.cargo_check(server.port()).context("failed to execute cargo check")?;
+        self.cargo_clean(target_dir.as_deref()).context("failed to execute cargo clean")?;
+        let mut build_outputs = Vec::new();
+        let success = self
+            .cargo_check(server.port(), target, feature_set, target_dir.as_deref(), &mut build_outputs)
+            .context("failed to execute cargo check")?;
 
         // Process the results of the analysis.
-        let analysis = server.into_analysis().context("failed to retrieve analysis result")?;
+        let mut analysis = server.into_analysis().context("failed to retrieve analysis result")?;
+
+        // In best-effort mode, crates that fail to compile are analyzed anyway and flagged as partial. Cargo
+        // failing for any other reason (e.g. an invalid manifest) is still an error.
+        if !success && !(self.opts.best_effort && !analysis.partial_crates().is_empty()) {
+            return Err(anyhow!("process returned error exit status")).context("failed to execute cargo check");
+        }
+        self.analyze_manifests(&mut analysis).context("failed to analyze manifests")?;
+        self.analyze_cargo_config(&mut analysis).context("failed to analyze cargo configuration")?;
+        for path in &build_outputs {
+            let output = build_script::analyze_output(path).context("failed to analyze build script output")?;
+            analysis.add_cargo_file(output);
+        }
         Ok(analysis)
     }
 
-    fn cargo_clean(&self) -> Result<()> {
-        let mut command = Command::new("cargo");
-        let mut builder = command.arg("clean");
+    // The wrapper reports the manifest of each compiled crate. The workspace manifest is analyzed too,
+    // as some settings (e.g. `[patch]`) are only honored there.
+    fn analyze_manifests(&self, analysis: &mut Analysis) -> Result<()> {
+        let mut paths = analysis.manifest_dirs().into_iter().map(|dir| dir.join("Cargo.toml")).collect::<Vec<_>>();
+        let manifest_path = self.find_manifest_path()?;
+        let root = manifest_path.as_ref().map(|p| manifest::find_workspace_root(p).unwrap_or_else(|| p.clone()));
+        paths.extend(manifest_path);
+        paths.extend(root.clone());
+        paths.sort_unstable();
+        paths.dedup();
 
-        if let Some(path) = &self.opts.manifest_path {
-            builder = builder.arg("--manifest-path").arg(path);
+        // A manifest cargo can read but we can't (e.g. with newer syntax) only leaves its features out.
+        for path in paths {
+            match manifest::analyze(&path) {
+                Ok(file) => analysis.add_cargo_file(file),
+                Err(error) => analysis.skip_cargo_file(path, format!("{:#}", error)),
+            }
         }
-        if self.opts.quiet {
-            builder = builder.arg("--quiet");
+
+        // The lockfile is shared by the whole workspace.
+        let lockfile = root.as_ref().and_then(|p| p.parent()).map(|dir| dir.join("Cargo.lock"));
+        if let Some(path) = lockfile.filter(|p| p.is_file()) {
+            analysis.add_cargo_file(manifest::analyze_lockfile(&path)?);
         }
+        Ok(())
+    }
+
+    // Cargo reads its configuration based on the directory it's run from, not on the manifest path.
+    // Only the configuration that ships with the workspace is analyzed, as the rest (e.g. the one in `CARGO_HOME`)
+    // belongs to the machine that runs the analysis.
+    fn analyze_cargo_config(&self, analysis: &mut Analysis) -> Result<()> {
+        let cwd = env::current_dir()?;
+        let workspace_dir = match self.find_manifest_path()? {
+            Some(path) => {
+                let root = cwd.join(manifest::find_workspace_root(&path).unwrap_or(path));
+                root.parent().map(Path::to_path_buf).unwrap_or_else(|| cwd.clone())
+            },
+            None => cwd.clone(),
+        };
+
+        // NOTE: Files outside the workspace are not even read, as they may be invalid for the pinned cargo.
+        for path in cargo_config::discover_paths(&cwd).into_iter().filter(|path| path.starts_with(&workspace_dir)) {
+            let file = cargo_config::ConfigFile::read(path)?;
+            analysis.add_cargo_file(cargo_config::analyze(&file));
+        }
+        Ok(())
+    }
+
+    fn cargo_clean(&self, target_dir: Option<&Path>) -> Result<()> {
+        let mut command = self.cargo_command("clean", target_dir);
 
-        let exit_status = builder.spawn()?.wait()?;
+        let exit_status = command.spawn()?.wait()?;
         if !exit_status.success() {
             bail!("process returned error exit status")
         }
         Ok(())
     }
 
-    fn cargo_check(&self, server_port: u16) -> Result<()> {
-        fn path_to_wrapper(wrapper_path: Option<PathBuf>) -> Result<PathBuf> {
-            let path = match wrapper_path {
-                Some(path) => path,
-                None => {
-                    let mut path = env::current_exe()?;
-                    path.pop();
-                    path.push(WRAPPER_NAME);
-                    path
-                },
-            };
-            if !path.is_file() {
-                bail!("{} does not exist or is not a file", path.display());
-            }
-            Ok(path)
+    fn find_wrapper_path(&self) -> Result<PathBuf> {
+        let path = match &self.opts.wrapper_path {
+            Some(path) => path.clone(),
+            None => {
+                let mut path = env::current_exe()?;
+                path.pop();
+                path.push(WRAPPER_NAME);
+                path
+            },
+        };
+        if !path.is_file() {
+            bail!("{} does not exist or is not a file", path.display());
         }
+        Ok(path)
+    }
 
-        let toolchain = env!("MINVER_TOOLCHAIN");
-        let wrapper_path = path_to_wrapper(self.opts.wrapper_path.clone()) //
-            .context("could not find compiler wrapper")?;
+    fn cargo_check(
+        &self,
+        server_port: u16,
+        target: Option<&str>,
+        feature_set: Option<&[String]>,
+        target_dir: Option<&Path>,
+        build_outputs: &mut Vec<PathBuf>,
+    ) -> Result<bool> {
+        // The messages of cargo are read as JSON (see below), so their format can't be chosen.
+        let message_format =
+            self.opts.cargo_args.iter().find(|a| *a == "--message-format" || a.starts_with("--message-format="));
+        if let Some(arg) = message_format {
+            bail!("`{}` is not supported, as the messages of cargo must be read as JSON", arg);
+        }
 
-        let mut command = Command::new("cargo");
-        let mut builder = command
-            .env(WRAPPER_ENV, wrapper_path)
-            .env(SERVER_PORT_ENV, server_port.to_string())
-            .arg(toolchain)
-            .arg("check");
+        let wrapper_path = self.find_wrapper_path().context("could not find compiler wrapper")?;
 
-        if let Some(path) = &self.opts.manifest_path {
-            builder = builder.arg("--manifest-path").arg(path);
+        // If the user already has a compiler wrapper (e.g. sccache), forward it to ours so that it can compose with it.
+        let cwd = env::current_dir()?;
+        let user_wrapper = cargo_config::rustc_wrapper(&cwd) //
+            .context("could not detect existing compiler wrapper")?
+            .filter(|path| path != &wrapper_path && path.file_stem().map_or(true, |stem| stem != WRAPPER_NAME));
+
+        // Tell the wrapper which cfgs are set by probing the compiler, so that the code they gate is reported apart.
+        let mut probe_cfgs = Vec::new();
+        for (cfg, version) in &self.config()?.probe_cfgs {
+            let version = feature::parse_rust_version(version)
+                .with_context(|| format!("invalid version {} for probe cfg {}", version, cfg))?;
+            probe_cfgs.push(format!("{}={}", cfg, version));
         }
-        if self.opts.quiet {
-            builder = builder.arg("--quiet");
+
+        let mut command = self.cargo_command("check", target_dir);
+        let mut builder = command.env(WRAPPER_ENV, wrapper_path).env(SERVER_PORT_ENV, server_port.to_string());
+
+        builder = match user_wrapper {
+            Some(path) => builder.env(RUSTC_WRAPPER_ENV, path),
+            None => builder.env_remove(RUSTC_WRAPPER_ENV),
+        };
+
+        if self.opts.verbose {
+            builder = builder.env(VERBOSE_ENV, "1");
+        }
+        builder = builder.env(PROBE_CFGS_ENV, probe_cfgs.join(","));
+        for spec in &self.opts.packages {
+            builder = builder.arg("--package").arg(spec);
+        }
+        if self.opts.workspace {
+            builder = builder.arg("--workspace");
+        }
+        for spec in &self.opts.exclude {
+            builder = builder.arg("--exclude").arg(spec);
         }
         if let Some(features) = self.opts.features.as_ref() {
             builder = builder.arg("--features").arg(features);
@@ -179,20 +605,202 @@ impl Driver {
         if self.opts.no_default_features {
             builder = builder.arg("--no-default-features");
         }
-        if self.opts.tests {
-            builder = builder.arg("--tests");
+        if let Some(feature_set) = feature_set {
+            builder = builder.arg("--no-default-features");
+            if !feature_set.is_empty() {
+                builder = builder.arg("--features").arg(feature_set.join(" "));
+            }
+        }
+        if self.opts.lib {
+            builder = builder.arg("--lib");
+        }
+        for name in &self.opts.bin {
+            builder = builder.arg("--bin").arg(name);
+        }
+        if self.opts.bins {
+            builder = builder.arg("--bins");
+        }
+        for name in &self.opts.example {
+            builder = builder.arg("--example").arg(name);
         }
         if self.opts.examples {
             builder = builder.arg("--examples");
         }
+        for name in &self.opts.test {
+            builder = builder.arg("--test").arg(name);
+        }
+        if self.opts.tests {
+            builder = builder.arg("--tests");
+        }
+        for name in &self.opts.bench {
+            builder = builder.arg("--bench").arg(name);
+        }
         if self.opts.benches {
             builder = builder.arg("--benches");
         }
+        if self.opts.all_targets {
+            builder = builder.arg("--all-targets");
+        }
+        if self.opts.release {
+            builder = builder.arg("--release");
+        }
+        if let Some(target) = target {
+            builder = builder.arg("--target").arg(target);
+        }
+        if let Some(jobs) = self.opts.jobs {
+            builder = builder.arg("--jobs").arg(jobs.to_string());
+        }
+        if self.opts.best_effort {
+            builder = builder.env(BEST_EFFORT_ENV, "1");
+        }
+        if self.opts.fast {
+            builder = builder.env(FAST_ENV, "1");
+        }
+        // Cargo reports where the output of each build script is saved, but diagnostics are still rendered.
+        builder = builder.arg("--message-format=json-render-diagnostics").stdout(Stdio::piped());
+        builder = builder.args(&self.opts.cargo_args);
 
-        let exit_status = builder.spawn()?.wait()?;
-        if !exit_status.success() {
-            bail!("process returned error exit status")
+        let mut child = builder.spawn()?;
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines() {
+                let message = match serde_json::from_str::<serde_json::Value>(&line?) {
+                    Ok(message) => message,
+                    Err(_) => continue,
+                };
+                if message["reason"] == "build-script-executed" {
+                    if let Some(out_dir) = message["out_dir"].as_str() {
+                        build_outputs.push(Path::new(out_dir).with_file_name("output"));
+                    }
+                }
+            }
         }
-        Ok(())
+
+        // NOTE: Whether cargo failing is an error depends on the analysis (see `analyze`).
+        let exit_status = child.wait()?;
+        Ok(exit_status.success())
+    }
+
+    // Creates a cargo command for the pinned toolchain, with the options that apply to any subcommand.
+    fn cargo_command(&self, subcommand: &str, target_dir: Option<&Path>) -> Command {
+        let toolchain = env!("MINVER_TOOLCHAIN");
+
+        // NOTE: When running as a cargo subcommand, CARGO points to the binary of a specific toolchain, which
+        // does not understand the `+toolchain` syntax. Rustup is used to set up the pinned toolchain instead.
+        // This runs the user's cargo against the pinned rustc: the wrapper must be run by the rustc it is linked
+        // against, while the cargo features of the package (e.g. the manifest and lockfile format) are those of
+        // the cargo that invoked us. A cargo much newer than the pinned toolchain may pass the compiler flags it
+        // does not know, which shows as a compile error.
+        let mut command = match env::var_os(CARGO_ENV) {
+            Some(cargo) => {
+                let mut command = Command::new("rustup");
+                command.arg("run").arg(toolchain.trim_start_matches('+')).arg(cargo);
+                command
+            },
+            None => {
+                let mut command = Command::new("cargo");
+                command.arg(toolchain);
+                command
+            },
+        };
+
+        let mut builder = command.arg(subcommand);
+        if let Some(path) = &self.opts.manifest_path {
+            builder = builder.arg("--manifest-path").arg(path);
+        }
+        if let Some(path) = target_dir {
+            builder = builder.arg("--target-dir").arg(path);
+        }
+        if self.opts.quiet {
+            builder = builder.arg("--quiet");
+        }
+        if self.opts.verbose {
+            builder = builder.arg("--verbose");
+        }
+        if self.opts.frozen {
+            builder = builder.arg("--frozen");
+        }
+        if self.opts.locked {
+            builder = builder.arg("--locked");
+        }
+        if self.opts.offline {
+            builder = builder.arg("--offline");
+        }
+        for flag in &self.opts.unstable_flags {
+            builder = builder.arg("-Z").arg(flag);
+        }
+        command
+    }
+}
+
+// Splits a package spec into a name and an optional version.
+// NOTE: Only specs made of a name and an optional version (e.g. `foo@1.0.0` or `foo:1.0.0`) are supported.
+fn parse_package_spec(spec: &str) -> (&str, Option<&str>) {
+    match spec.find(|c| c == '@' || c == ':') {
+        Some(pos) => (&spec[..pos], Some(&spec[pos + 1..])),
+        None => (spec, None),
+    }
+}
+
+// Finds the manifest of a workspace member in the output of `cargo metadata`.
+fn find_package_manifest(metadata: &serde_json::Value, spec: &str) -> Option<PathBuf> {
+    let (name, version) = parse_package_spec(spec);
+    let packages = metadata["packages"].as_array().map_or(&[][..], Vec::as_slice);
+    packages
+        .iter()
+        .find(|package| package["name"] == name && version.map_or(true, |v| package["version"] == v))
+        .and_then(|package| package["manifest_path"].as_str())
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cargo_args(driver: &Driver) -> Vec<String> {
+        let command = driver.cargo_command("check", Some(Path::new("target/minver")));
+        let args = command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect::<Vec<_>>();
+        args.iter().skip_while(|arg| *arg != "check").skip(1).cloned().collect()
+    }
+
+    #[test]
+    fn unstable_flags() {
+        let options = Options::from_iter(&["cargo-minver", "-Z", "unstable-options", "-Zbuild-std", "--offline"]);
+        let expected = ["--target-dir", "target/minver", "--offline", "-Z", "unstable-options", "-Z", "build-std"];
+        assert_eq!(expected.to_vec(), cargo_args(&Driver::from(options)));
+
+        let mut driver = Driver::default();
+        driver.unstable_flag("minimal-versions");
+        assert_eq!(["--target-dir", "target/minver", "-Z", "minimal-versions"].to_vec(), cargo_args(&driver));
+    }
+
+    #[test]
+    fn extra_cargo_args() {
+        let options = Options::from_iter(&["cargo-minver", "--", "-Z", "foo", "--config", "build.jobs=1"]);
+        assert!(options.unstable_flags.is_empty());
+        assert_eq!(vec!["-Z", "foo", "--config", "build.jobs=1"], options.cargo_args);
+    }
+
+    #[test]
+    fn package_specs() {
+        assert_eq!(("foo", None), parse_package_spec("foo"));
+        assert_eq!(("foo", Some("1.0.0")), parse_package_spec("foo:1.0.0"));
+        assert_eq!(("foo", Some("1.0.0")), parse_package_spec("foo@1.0.0"));
+    }
+
+    #[test]
+    fn package_manifests() {
+        let metadata = serde_json::json!({
+            "packages": [
+                { "name": "foo", "version": "1.0.0", "manifest_path": "/ws/foo/Cargo.toml" },
+                { "name": "foo", "version": "2.0.0", "manifest_path": "/ws/foo2/Cargo.toml" },
+                { "name": "bar", "version": "0.1.0", "manifest_path": "/ws/bar/Cargo.toml" },
+            ]
+        });
+
+        assert_eq!(Some(PathBuf::from("/ws/bar/Cargo.toml")), find_package_manifest(&metadata, "bar"));
+        assert_eq!(Some(PathBuf::from("/ws/foo2/Cargo.toml")), find_package_manifest(&metadata, "foo:2.0.0"));
+        assert_eq!(Some(PathBuf::from("/ws/foo/Cargo.toml")), find_package_manifest(&metadata, "foo@1.0.0"));
+        assert_eq!(None, find_package_manifest(&metadata, "foo:3.0.0"));
+        assert_eq!(None, find_package_manifest(&metadata, "baz"));
     }
 }
@@ -1,5 +1,6 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
 
 use semver::Version;
 use serde::{Deserialize, Serialize};
@@ -11,6 +12,62 @@ use serde::{Deserialize, Serialize};
 pub enum FeatureKind {
     Lang,
     Lib,
+    Cargo,
+}
+
+/// Features whose stabilization version depends on the edition, as (name, since on 2015, since on 2018).
+/// Features that only exist on the 2018 edition have no 2015 version.
+const EDITION_DEPENDENT_FEATURES: &[(&str, Option<&str>, &str)] = &[
+    ("async_await", None, "1.39.0"),
+    // The 2018 edition itself was stabilized after `crate::` paths were accepted on 2015.
+    ("crate_in_paths", Some("1.30.0"), "1.31.0"),
+    ("macro_at_most_once_rep", Some("1.37.0"), "1.32.0"),
+    ("nll", Some("1.36.0"), "1.31.0"),
+    ("two_phase_borrows", Some("1.36.0"), "1.31.0"),
+    ("uniform_paths", None, "1.32.0"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Edition {
+    Edition2015,
+    Edition2018,
+}
+
+impl Default for Edition {
+    fn default() -> Self {
+        Edition::Edition2015
+    }
+}
+
+impl Edition {
+    /// Returns the implicit requirement of crates using this edition, if any.
+    pub fn feature(self) -> Option<Feature> {
+        match self {
+            Edition::Edition2015 => None,
+            Edition::Edition2018 => Some(Feature {
+                name: "edition2018".into(),
+                kind: FeatureKind::Lang,
+                since: Some(Version::new(1, 31, 0)),
+            }),
+        }
+    }
+
+    /// Returns the stabilization version of a feature for this edition, if it differs between editions
+    /// and the feature exists on this edition.
+    pub fn feature_since(self, name: &str) -> Option<Version> {
+        let (_, since_2015, since_2018) = EDITION_DEPENDENT_FEATURES.iter().find(|(feature, ..)| *feature == name)?;
+        let since = if self == Edition::Edition2015 { *since_2015 } else { Some(*since_2018) };
+        since.map(|since| since.parse().unwrap())
+    }
+}
+
+impl Display for Edition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Edition::Edition2015 => write!(f, "2015"),
+            Edition::Edition2018 => write!(f, "2018"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -29,46 +86,314 @@ pub struct Span {
     pub end_col: usize,
 }
 
+impl Span {
+    pub fn contains(&self, other: &Span) -> bool {
+        self.file_name == other.file_name
+            && (self.start_line, self.start_col) <= (other.start_line, other.start_col)
+            && (self.end_line, self.end_col) >= (other.end_line, other.end_col)
+    }
+}
+
 impl Display for Span {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{} {}:{} {}:{}", self.file_name, self.start_line, self.start_col, self.end_line, self.end_col)
     }
 }
 
+/// A condition that makes code be compiled only by some compilers, e.g. `rustversion::since(1.36)`
+/// or a `cfg` set by a build script that probes the compiler.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Gate {
+    pub condition: String,
+    /// The oldest compiler that compiles the gated code.
+    pub version: Version,
+}
+
+/// A feature use in code that is only compiled by the compilers that satisfy a gate.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ConditionalUse {
+    pub span: Span,
+    pub gate: Gate,
+}
+
+impl Display for ConditionalUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (gated by {})", self.span, self.gate.condition)
+    }
+}
+
+/// Parses a Rust version the way it's usually written, where the patch number is optional (e.g. `1.36`).
+pub fn parse_rust_version(value: &str) -> Option<Version> {
+    let value = value.trim();
+    match value.split('.').count() {
+        2 => format!("{}.0", value).parse().ok(),
+        _ => value.parse().ok(),
+    }
+}
+
+/// A feature use in code disabled by a `cfg` predicate that does not hold for the analyzed configuration.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct InactiveUse {
+    pub span: Span,
+    pub cfg: String,
+}
+
+impl Display for InactiveUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (seen in inactive cfg({}))", self.span, self.cfg)
+    }
+}
+
+/// A feature use along with the targets it was seen for. Uses in files read by cargo belong to no target.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetUse<'a> {
+    pub span: &'a Span,
+    pub targets: Vec<&'a str>,
+}
+
+impl Display for TargetUse<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.span)?;
+        if !self.targets.is_empty() {
+            write!(f, " [{}]", self.targets.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct CrateAnalysis {
     pub name: String,
+    pub manifest_dir: Option<PathBuf>,
+    pub target: Option<String>,
+    pub edition: Edition,
+    pub partial: bool,
+    pub unchecked: Vec<Feature>,
+    pub features: Vec<Feature>,
+    pub uses: HashMap<String, Vec<Span>>,
+    pub inactive_features: Vec<Feature>,
+    pub inactive_uses: HashMap<String, Vec<InactiveUse>>,
+    pub conditional_features: Vec<Feature>,
+    pub conditional_uses: HashMap<String, Vec<ConditionalUse>>,
+}
+
+/// The features a file read by cargo (a manifest, a configuration file or a lockfile) requires from it.
+#[derive(Debug, Default)]
+pub struct CargoFileAnalysis {
+    pub path: PathBuf,
     pub features: Vec<Feature>,
     pub uses: HashMap<String, Vec<Span>>,
 }
 
-#[derive(Debug)]
+impl CargoFileAnalysis {
+    pub(crate) fn add_line_use(&mut self, feature: Feature, line: usize, len: usize) {
+        let span = Span {
+            file_name: self.path.display().to_string(),
+            start_line: line,
+            start_col: 0,
+            end_line: line,
+            end_col: len,
+        };
+        self.add_use(feature, Some(span));
+    }
+
+    /// Records a feature along with where it's used, if that's known.
+    pub(crate) fn add_use(&mut self, feature: Feature, span: Option<Span>) {
+        if let Some(span) = span {
+            let uses = self.uses.entry(feature.name.clone()).or_default();
+            if !uses.contains(&span) {
+                uses.push(span);
+            }
+        }
+        if !self.features.contains(&feature) {
+            self.features.push(feature);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct Analysis {
     crates: Vec<CrateAnalysis>,
+    cargo_files: Vec<CargoFileAnalysis>,
+    // Cargo files that could not be analyzed, along with why.
+    skipped_cargo_files: Vec<(PathBuf, String)>,
 }
 
 impl From<Vec<CrateAnalysis>> for Analysis {
     fn from(crates: Vec<CrateAnalysis>) -> Self {
-        Self { crates }
+        Self { crates, ..Default::default() }
     }
 }
 
 impl Analysis {
+    pub fn merge(&mut self, other: Analysis) {
+        self.crates.extend(other.crates);
+        for file in other.cargo_files {
+            self.add_cargo_file(file);
+        }
+        for (path, reason) in other.skipped_cargo_files {
+            self.skip_cargo_file(path, reason);
+        }
+    }
+
+    pub(crate) fn add_cargo_file(&mut self, file: CargoFileAnalysis) {
+        if self.cargo_files.iter().all(|f| f.path != file.path) {
+            self.cargo_files.push(file);
+        }
+    }
+
+    pub(crate) fn skip_cargo_file(&mut self, path: PathBuf, reason: String) {
+        if self.skipped_cargo_files.iter().all(|(p, _)| *p != path) {
+            self.skipped_cargo_files.push((path, reason));
+        }
+    }
+
+    /// Returns the cargo files that could not be analyzed, along with why.
+    pub fn skipped_cargo_files(&self) -> Vec<(&Path, &str)> {
+        self.skipped_cargo_files.iter().map(|(path, reason)| (path.as_path(), reason.as_str())).collect()
+    }
+
+    pub(crate) fn manifest_dirs(&self) -> Vec<&PathBuf> {
+        let mut dirs = self.crates.iter().filter_map(|a| a.manifest_dir.as_ref()).collect::<Vec<_>>();
+        dirs.sort_unstable();
+        dirs.dedup();
+        dirs
+    }
+
+    // Cargo files are shared by all targets, so their features are part of the analysis of each of them.
+    fn features_of<'a>(&'a self, crates: impl Iterator<Item = &'a CrateAnalysis>) -> impl Iterator<Item = &'a Feature> {
+        crates.map(|a| &a.features).chain(self.cargo_files.iter().map(|f| &f.features)).flatten()
+    }
+
+    pub(crate) fn tag_target(&mut self, target: &str) {
+        for krate in &mut self.crates {
+            krate.target = Some(target.into());
+        }
+    }
+
+    pub fn targets(&self) -> Vec<&str> {
+        let mut targets = self.crates.iter().filter_map(|a| a.target.as_deref()).collect::<Vec<_>>();
+        targets.sort_unstable();
+        targets.dedup();
+        targets
+    }
+
+    pub fn minimum_version(&self) -> Option<&Version> {
+        self.features_of(self.crates.iter()).filter_map(|f| f.since.as_ref()).max()
+    }
+
+    pub fn target_minimum_version(&self, target: &str) -> Option<&Version> {
+        self.features_of(self.crates.iter().filter(|a| a.target.as_deref() == Some(target)))
+            .filter_map(|f| f.since.as_ref())
+            .max()
+    }
+
     pub fn feature(&self, name: &str) -> Option<&Feature> {
-        self.crates.iter().map(|a| &a.features).flatten().find(|f| f.name == name)
+        self.features_of(self.crates.iter()).find(|f| f.name == name)
     }
 
     pub fn all_features(&self) -> Vec<&Feature> {
-        let mut features = self.crates.iter().map(|a| &a.features).flatten().collect::<Vec<_>>();
+        let mut features = self.features_of(self.crates.iter()).collect::<Vec<_>>();
 
         features.sort_unstable_by(|a, b| if a.since == b.since { a.name.cmp(&b.name) } else { b.since.cmp(&a.since) });
         features.dedup();
         features
     }
 
+    pub fn crate_edition(&self, name: &str) -> Option<Edition> {
+        self.crates.iter().find(|a| a.name == name).map(|a| a.edition)
+    }
+
+    pub fn crate_names(&self) -> Vec<&str> {
+        let mut names = self.crates.iter().map(|a| a.name.as_str()).collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    pub fn partial_crates(&self) -> Vec<&str> {
+        self.crates.iter().filter(|a| a.partial).map(|a| a.name.as_str()).collect()
+    }
+
+    pub fn unchecked_features(&self) -> Vec<&Feature> {
+        let mut features = self.crates.iter().map(|a| &a.unchecked).flatten().collect::<Vec<_>>();
+        features.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        features.dedup();
+        features
+    }
+
     pub fn all_feature_uses(&self, name: &str) -> Vec<&Span> {
-        let mut uses = self.crates.iter().map(|a| a.uses.get(name)).flatten().flatten().collect::<Vec<_>>();
+        let crate_uses = self.crates.iter().map(|a| a.uses.get(name));
+        let file_uses = self.cargo_files.iter().map(|f| f.uses.get(name));
+        let mut uses = crate_uses.chain(file_uses).flatten().flatten().collect::<Vec<_>>();
         uses.sort();
+        uses.dedup();
         uses
     }
+
+    pub fn all_feature_uses_by_target(&self, name: &str) -> Vec<TargetUse<'_>> {
+        let mut uses = BTreeMap::<_, Vec<_>>::new();
+        for krate in &self.crates {
+            for span in krate.uses.get(name).into_iter().flatten() {
+                let targets = uses.entry(span).or_default();
+                targets.extend(krate.target.as_deref());
+            }
+        }
+        for file in &self.cargo_files {
+            for span in file.uses.get(name).into_iter().flatten() {
+                uses.entry(span).or_default();
+            }
+        }
+
+        uses.into_iter()
+            .map(|(span, mut targets)| {
+                targets.sort_unstable();
+                targets.dedup();
+                TargetUse { span, targets }
+            })
+            .collect()
+    }
+
+    pub fn inactive_features(&self) -> Vec<&Feature> {
+        let mut features = self.crates.iter().map(|a| &a.inactive_features).flatten().collect::<Vec<_>>();
+
+        features.sort_unstable_by(|a, b| if a.since == b.since { a.name.cmp(&b.name) } else { b.since.cmp(&a.since) });
+        features.dedup();
+        features
+    }
+
+    pub fn inactive_feature_uses(&self, name: &str) -> Vec<&InactiveUse> {
+        let mut uses = self.crates.iter().map(|a| a.inactive_uses.get(name)).flatten().flatten().collect::<Vec<_>>();
+        uses.sort();
+        uses.dedup();
+        uses
+    }
+
+    pub fn conditional_features(&self) -> Vec<&Feature> {
+        let mut features = self.crates.iter().map(|a| &a.conditional_features).flatten().collect::<Vec<_>>();
+
+        features.sort_unstable_by(|a, b| if a.since == b.since { a.name.cmp(&b.name) } else { b.since.cmp(&a.since) });
+        features.dedup();
+        features
+    }
+
+    pub fn conditional_feature_uses(&self, name: &str) -> Vec<&ConditionalUse> {
+        let mut uses = self.crates.iter().map(|a| a.conditional_uses.get(name)).flatten().flatten().collect::<Vec<_>>();
+        uses.sort();
+        uses.dedup();
+        uses
+    }
+
+    /// Returns the conditional uses of features that were stabilized after the version their gate allows.
+    pub fn gate_violations(&self) -> Vec<(&Feature, &ConditionalUse)> {
+        self.conditional_features()
+            .into_iter()
+            .flat_map(|feature| {
+                self.conditional_feature_uses(&feature.name)
+                    .into_iter()
+                    .filter(move |u| feature.since.as_ref() > Some(&u.gate.version))
+                    .map(move |u| (feature, u))
+            })
+            .collect()
+    }
 }
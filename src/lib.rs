@@ -1,10 +1,29 @@
 // TODO: documentation
 
+mod attribution;
+mod build_script;
+mod cargo_config;
+mod config;
+mod coverage;
 mod driver;
 mod feature;
 pub mod ipc;
+mod manifest;
+mod powerset;
+mod toml_spans;
 
 pub const SERVER_PORT_ENV: &str = "MINVER_SERVER_PORT";
+/// Makes the wrapper print the accepted lang features of the toolchain as JSON, along with how they are detected.
+pub const COVERAGE_ARG: &str = "--minver-coverage";
+pub const BEST_EFFORT_ENV: &str = "MINVER_BEST_EFFORT";
+pub const FAST_ENV: &str = "MINVER_FAST";
+pub const PROBE_CFGS_ENV: &str = "MINVER_PROBE_CFGS";
+pub const RUSTC_WRAPPER_ENV: &str = "MINVER_RUSTC_WRAPPER";
+pub const VERBOSE_ENV: &str = "MINVER_VERBOSE";
 
+pub use attribution::{attribute, markdown_table, FeatureAttribution};
+pub use build_script::directive_features;
+pub use coverage::{AcceptedFeature, CoverageReport, Detection, FeatureCoverage};
 pub use driver::{Driver, Options};
 pub use feature::*;
+pub use powerset::{describe_feature_set, worst_case, FeatureSetAnalysis};
@@ -0,0 +1,393 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use toml::Value;
+
+use crate::feature::{CargoFileAnalysis, Feature, FeatureKind};
+use crate::toml_spans::TomlSpans;
+
+const MANIFEST_NAME: &str = "Cargo.toml";
+const DEPENDENCY_TABLES: [&str; 2] = ["dependencies", "build-dependencies"];
+const ALL_DEPENDENCY_TABLES: [&str; 5] =
+    ["dependencies", "dev-dependencies", "dev_dependencies", "build-dependencies", "build_dependencies"];
+const BUILTIN_PROFILES: [&str; 5] = ["dev", "release", "test", "bench", "doc"];
+
+/// Finds the manifest cargo would use when run from `cwd` without `--manifest-path`.
+pub fn find(cwd: &Path) -> Option<PathBuf> {
+    cwd.ancestors().map(|dir| dir.join(MANIFEST_NAME)).find(|path| path.is_file())
+}
+
+pub fn read(path: &Path) -> Result<Value> {
+    let contents = fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+    let manifest = contents.parse().with_context(|| format!("could not parse {}", path.display()))?;
+    Ok(manifest)
+}
+
+/// Returns the cargo features of the package, including the implicit ones of optional dependencies.
+/// The `default` feature is not included.
+pub fn package_features(path: &Path) -> Result<Vec<String>> {
+    let manifest = read(path)?;
+    if manifest.get("package").is_none() {
+        bail!("{} is not the manifest of a package", path.display());
+    }
+    Ok(manifest_features(&manifest))
+}
+
+fn manifest_features(manifest: &Value) -> Vec<String> {
+    let mut features = match manifest.get("features").and_then(Value::as_table) {
+        Some(table) => table.keys().filter(|k| *k != "default").cloned().collect(),
+        None => Vec::new(),
+    };
+
+    let platforms = manifest.get("target").and_then(Value::as_table).into_iter().flat_map(|t| t.values());
+    let tables = Some(manifest).into_iter().chain(platforms).collect::<Vec<_>>();
+    for deps in tables
+        .iter()
+        .flat_map(|table| DEPENDENCY_TABLES.iter().filter_map(move |key| table.get(key)))
+        .filter_map(Value::as_table)
+    {
+        for (name, dep) in deps {
+            if let Some(true) = dep.get("optional").and_then(Value::as_bool) {
+                features.push(name.clone());
+            }
+        }
+    }
+
+    features.sort_unstable();
+    features.dedup();
+    features
+}
+
+/// Finds the manifest of the workspace the package belongs to, if it's not the package manifest itself.
+pub fn find_workspace_root(path: &Path) -> Option<PathBuf> {
+    let package_dir = path.parent()?;
+    package_dir
+        .ancestors()
+        .skip(1)
+        .map(|dir| dir.join(MANIFEST_NAME))
+        .filter(|path| path.is_file())
+        .find(|path| read(path).map_or(false, |manifest| manifest.get("workspace").is_some()))
+}
+
+/// Finds the manifest features that need a minimum version of cargo.
+pub fn analyze(path: &Path) -> Result<CargoFileAnalysis> {
+    let contents = fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+    analyze_contents(path, &contents)
+}
+
+fn analyze_contents(path: &Path, contents: &str) -> Result<CargoFileAnalysis> {
+    let manifest = contents.parse::<Value>().with_context(|| format!("could not parse {}", path.display()))?;
+
+    let analysis = CargoFileAnalysis { path: path.into(), ..Default::default() };
+    let spans = TomlSpans::new(&path.display().to_string(), contents);
+    let mut analyzer = Analyzer { spans, analysis };
+    analyzer.check_package(&manifest);
+    analyzer.check_dependencies(&manifest);
+    analyzer.check_features(&manifest);
+    analyzer.check_patch(&manifest);
+    analyzer.check_profiles(&manifest);
+    Ok(analyzer.analysis)
+}
+
+struct Analyzer {
+    spans: TomlSpans,
+    analysis: CargoFileAnalysis,
+}
+
+impl Analyzer {
+    fn check_package(&mut self, manifest: &Value) {
+        for key in &["package", "workspace"] {
+            if let Some("2") = manifest.get(key).and_then(|t| t.get("resolver")).and_then(Value::as_str) {
+                self.record("resolver2", "1.51.0", &[key, "resolver"]);
+            }
+        }
+
+        if let Some(package) = manifest.get("package").and_then(Value::as_table) {
+            if package.contains_key("rust-version") {
+                self.record("rust_version", "1.56.0", &["package", "rust-version"]);
+            }
+            for (key, _) in package.iter().filter(|(_, value)| inherits_from_workspace(value)) {
+                self.record("workspace_inheritance", "1.64.0", &["package", key, "workspace"]);
+            }
+        }
+    }
+
+    fn check_dependencies(&mut self, manifest: &Value) {
+        let platforms = manifest.get("target").and_then(Value::as_table).into_iter().flatten();
+        let tables = Some((vec![], manifest))
+            .into_iter()
+            .chain(platforms.map(|(name, platform)| (vec!["target", name.as_str()], platform)))
+            .collect::<Vec<_>>();
+
+        for (prefix, table) in &tables {
+            for key in &ALL_DEPENDENCY_TABLES {
+                let dependencies = table.get(key).and_then(Value::as_table).into_iter().flatten();
+                for (name, dependency) in dependencies {
+                    let path = |last| prefix.iter().copied().chain(vec![*key, name.as_str(), last]).collect::<Vec<_>>();
+                    if dependency.get("package").and_then(Value::as_str).is_some() {
+                        self.record("rename_dependency", "1.31.0", &path("package"));
+                    }
+                    if inherits_from_workspace(dependency) {
+                        self.record("workspace_inheritance", "1.64.0", &path("workspace"));
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_features(&mut self, manifest: &Value) {
+        let features = manifest.get("features").and_then(Value::as_table).into_iter().flatten();
+        for (feature, values) in features {
+            let values = values.as_array().into_iter().flatten().filter_map(Value::as_str);
+            for (index, value) in values.enumerate() {
+                let index = index.to_string();
+                if value.starts_with("dep:") {
+                    self.record("namespaced_features", "1.60.0", &["features", feature, &index]);
+                }
+                if value.contains("?/") {
+                    self.record("weak_dependency_features", "1.60.0", &["features", feature, &index]);
+                }
+            }
+        }
+    }
+
+    fn check_patch(&mut self, manifest: &Value) {
+        if manifest.get("patch").is_some() {
+            self.record("patch", "1.21.0", &["patch"]);
+        }
+    }
+
+    fn check_profiles(&mut self, manifest: &Value) {
+        let profiles = match manifest.get("profile").and_then(Value::as_table) {
+            Some(profiles) => profiles,
+            None => return,
+        };
+
+        for (name, profile) in profiles {
+            for key in &["package", "build-override"] {
+                if profile.get(key).is_some() {
+                    self.record("profile_overrides", "1.41.0", &["profile", name, key]);
+                }
+            }
+            if !BUILTIN_PROFILES.contains(&name.as_str()) {
+                self.record("named_profiles", "1.57.0", &["profile", name]);
+            }
+        }
+    }
+
+    fn record(&mut self, name: &str, since: &str, path: &[&str]) {
+        record_use(&mut self.analysis, &self.spans, name, since, path);
+    }
+}
+
+/// Records the use of a cargo feature in a file, at the entry found under the given path of keys
+/// (see `TomlSpans`). If the entry has no known span (e.g. the path is empty), the use has none either.
+pub(crate) fn record_use(analysis: &mut CargoFileAnalysis, spans: &TomlSpans, name: &str, since: &str, path: &[&str]) {
+    let feature = Feature { name: name.into(), kind: FeatureKind::Cargo, since: since.parse().ok() };
+    let span = if path.is_empty() { None } else { spans.get(path) };
+    analysis.add_use(feature, span);
+}
+
+fn inherits_from_workspace(value: &Value) -> bool {
+    value.get("workspace").and_then(Value::as_bool) == Some(true)
+}
+
+/// Finds the cargo version needed to read a lockfile, based on its format.
+pub fn analyze_lockfile(path: &Path) -> Result<CargoFileAnalysis> {
+    let contents = fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+    analyze_lockfile_contents(path, &contents)
+}
+
+fn analyze_lockfile_contents(path: &Path, contents: &str) -> Result<CargoFileAnalysis> {
+    let lockfile = contents.parse::<Value>().with_context(|| format!("could not parse {}", path.display()))?;
+
+    let mut analysis = CargoFileAnalysis { path: path.into(), ..Default::default() };
+    let spans = TomlSpans::new(&path.display().to_string(), contents);
+    // NOTE: Versions 1 and 2 are not marked, but only version 2 keeps the checksums inline in the packages.
+    // A lockfile without any checksums (e.g. only path dependencies) reads the same in both.
+    match lockfile.get("version").and_then(Value::as_integer) {
+        Some(version @ 3..=4) => {
+            let since = if version == 3 { "1.47.0" } else { "1.78.0" };
+            record_use(&mut analysis, &spans, &format!("lockfile_v{}", version), since, &["version"]);
+        },
+        None => {
+            if let Some(index) = first_inline_checksum(&lockfile) {
+                record_use(
+                    &mut analysis,
+                    &spans,
+                    "lockfile_v2",
+                    "1.41.0",
+                    &["package", &index.to_string(), "checksum"],
+                );
+            }
+        },
+        _ => {},
+    }
+    Ok(analysis)
+}
+
+// Returns the index of the first package with an inline checksum, if any.
+fn first_inline_checksum(lockfile: &Value) -> Option<usize> {
+    let packages = lockfile.get("package").and_then(Value::as_array)?;
+    packages.iter().position(|package| package.get("checksum").is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn implicit_features() {
+        let manifest = r#"
+[package]
+name = "foo"
+version = "0.1.0"
+
+[features]
+default = ["std"]
+std = []
+
+[dependencies]
+serde = { version = "1.0", optional = true }
+log = "0.4"
+
+[build-dependencies]
+cc = { version = "1.0", optional = true }
+
+[dev-dependencies]
+quickcheck = { version = "0.9", optional = true }
+
+[target.'cfg(unix)'.dependencies]
+libc = { version = "0.2", optional = true }
+"#;
+        let manifest = manifest.parse::<Value>().unwrap();
+        // Dev-dependencies can't be optional, so they are not features.
+        assert_eq!(vec!["cc", "libc", "serde", "std"], manifest_features(&manifest));
+    }
+
+    // Returns each feature used by a manifest, along with its version and where it's used.
+    fn analyze_manifest(contents: &str) -> Vec<(String, String, Vec<String>)> {
+        let analysis = analyze_contents(Path::new("Cargo.toml"), contents).unwrap();
+        let mut features = analysis
+            .features
+            .iter()
+            .map(|feature| {
+                let uses = analysis.uses.get(&feature.name).into_iter().flatten().map(ToString::to_string).collect();
+                (feature.name.clone(), feature.since.as_ref().unwrap().to_string(), uses)
+            })
+            .collect::<Vec<_>>();
+        features.sort();
+        features
+    }
+
+    fn feature(name: &str, since: &str, uses: &[&str]) -> (String, String, Vec<String>) {
+        (name.into(), since.into(), uses.iter().map(|span| format!("Cargo.toml {}", span)).collect())
+    }
+
+    #[test]
+    fn manifest_keys() {
+        let manifest = r#"[package]
+name = "foo"
+version = "0.1.0"
+rust-version = "1.56"
+edition.workspace = true
+resolver = "2"
+
+[dependencies]
+bar = { version = "1.0", package = "baz" }
+qux = { workspace = true }
+
+[target.'cfg(unix)'.build-dependencies]
+cc = { version = "1.0", package = "cc-rs" }
+
+[features]
+std = ["dep:bar", "qux?/std"]
+
+[patch.crates-io]
+baz = { path = "../baz" }
+
+[profile.dev.package.bar]
+opt-level = 3
+
+[profile.custom]
+inherits = "release"
+"#;
+        let expected = vec![
+            feature("named_profiles", "1.57.0", &["24:9 24:15"]),
+            feature("namespaced_features", "1.60.0", &["16:7 16:16"]),
+            feature("patch", "1.21.0", &["18:1 18:6"]),
+            feature("profile_overrides", "1.41.0", &["21:13 21:20"]),
+            feature("rename_dependency", "1.31.0", &["9:25 9:40", "13:24 13:41"]),
+            feature("resolver2", "1.51.0", &["6:0 6:14"]),
+            feature("rust_version", "1.56.0", &["4:0 4:21"]),
+            feature("weak_dependency_features", "1.60.0", &["16:18 16:28"]),
+            feature("workspace_inheritance", "1.64.0", &["5:8 5:24", "10:8 10:24"]),
+        ];
+        assert_eq!(expected, analyze_manifest(manifest));
+    }
+
+    fn analyze_lockfile(contents: &str) -> Vec<(String, String, Vec<String>)> {
+        let analysis = analyze_lockfile_contents(Path::new("Cargo.lock"), contents).unwrap();
+        analysis
+            .features
+            .iter()
+            .map(|feature| {
+                let uses = analysis.uses.get(&feature.name).into_iter().flatten().map(ToString::to_string).collect();
+                (feature.name.clone(), feature.since.as_ref().unwrap().to_string(), uses)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lockfile_versions() {
+        // Version 1 keeps the checksums in the metadata table.
+        let v1 = r#"[[package]]
+name = "foo"
+version = "0.1.0"
+dependencies = ["serde 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)"]
+
+[[package]]
+name = "serde"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[metadata]
+"checksum serde 1.0.0 (registry+https://github.com/rust-lang/crates.io-index)" = "0123"
+"#;
+        assert!(analyze_lockfile(v1).is_empty());
+
+        let v2 = r#"[[package]]
+name = "foo"
+version = "0.1.0"
+dependencies = ["serde"]
+
+[[package]]
+name = "serde"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "0123"
+"#;
+        let expected = vec![("lockfile_v2".into(), "1.41.0".into(), vec!["Cargo.lock 10:0 10:17".into()])];
+        assert_eq!(expected, analyze_lockfile(v2));
+
+        // Versions 1 and 2 read the same without any checksums.
+        assert!(analyze_lockfile("[[package]]\nname = \"foo\"\nversion = \"0.1.0\"\n").is_empty());
+
+        let expected = vec![("lockfile_v3".into(), "1.47.0".into(), vec!["Cargo.lock 1:0 1:11".into()])];
+        assert_eq!(expected, analyze_lockfile("version = 3\n\n[[package]]\nname = \"foo\"\nversion = \"0.1.0\"\n"));
+        let expected = vec![("lockfile_v4".into(), "1.78.0".into(), vec!["Cargo.lock 1:0 1:11".into()])];
+        assert_eq!(expected, analyze_lockfile("version = 4\n"));
+    }
+
+    #[test]
+    fn unparseable_manifest() {
+        assert!(analyze_contents(Path::new("Cargo.toml"), "[package\nname = \"foo\"").is_err());
+    }
+
+    #[test]
+    fn no_features() {
+        let manifest = "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n".parse::<Value>().unwrap();
+        assert!(manifest_features(&manifest).is_empty());
+    }
+}
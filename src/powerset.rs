@@ -0,0 +1,110 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::feature::Analysis;
+
+/// The analysis of a package with only a set of its cargo features enabled.
+#[derive(Debug)]
+pub struct FeatureSetAnalysis {
+    pub cargo_features: Vec<String>,
+    pub analysis: Analysis,
+}
+
+/// Describes a set of cargo features for the user, e.g. `std+serde` or `no features`.
+pub fn describe_feature_set(features: &[String]) -> String {
+    if features.is_empty() { "no features".into() } else { features.join("+") }
+}
+
+/// Returns a short name for a set of cargo features, e.g. for its target directory. Unlike the description of
+/// the set, its length doesn't depend on the number of features.
+pub fn feature_set_id(features: &[String]) -> String {
+    if features.is_empty() {
+        return "no-features".into();
+    }
+
+    let mut hasher = DefaultHasher::new();
+    features.hash(&mut hasher);
+    format!("features-{:016x}", hasher.finish())
+}
+
+/// Returns the set with no features, followed by a set for each feature on its own.
+pub fn each_feature(features: &[String]) -> Vec<Vec<String>> {
+    let mut sets = vec![Vec::new()];
+    sets.extend(features.iter().map(|f| vec![f.clone()]));
+    sets
+}
+
+/// Returns every combination of features with at most `depth` elements, the smallest ones first.
+pub fn powerset(features: &[String], depth: Option<usize>) -> Vec<Vec<String>> {
+    let depth = depth.unwrap_or_else(|| features.len());
+
+    let mut sets = vec![Vec::new()];
+    for feature in features {
+        let extended = sets
+            .iter()
+            .filter(|set| set.len() < depth)
+            .map(|set| {
+                let mut set = set.clone();
+                set.push(feature.clone());
+                set
+            })
+            .collect::<Vec<_>>();
+        sets.extend(extended);
+    }
+
+    sets.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+    sets
+}
+
+/// Returns the analysis of the set of features that requires the newest compiler.
+pub fn worst_case(analyses: &[FeatureSetAnalysis]) -> Option<&FeatureSetAnalysis> {
+    analyses.iter().max_by(|a, b| a.analysis.minimum_version().cmp(&b.analysis.minimum_version()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn no_features() {
+        assert_eq!(vec![Vec::<String>::new()], each_feature(&[]));
+        assert_eq!(vec![Vec::<String>::new()], powerset(&[], None));
+    }
+
+    #[test]
+    fn single_feature() {
+        let expected = vec![features(&[]), features(&["std"])];
+        assert_eq!(expected, each_feature(&features(&["std"])));
+        assert_eq!(expected, powerset(&features(&["std"]), None));
+        assert_eq!(vec![features(&[])], powerset(&features(&["std"]), Some(0)));
+    }
+
+    #[test]
+    fn several_features() {
+        let all = features(&["a", "b", "c"]);
+        assert_eq!(vec![features(&[]), features(&["a"]), features(&["b"]), features(&["c"])], each_feature(&all));
+
+        let sets = powerset(&all, None);
+        assert_eq!(8, sets.len());
+        assert_eq!(features(&["a", "b", "c"]), sets[7]);
+
+        let sets = powerset(&all, Some(2));
+        assert_eq!(7, sets.len());
+        assert_eq!(vec![features(&["a", "b"]), features(&["a", "c"]), features(&["b", "c"])], sets[4..].to_vec());
+    }
+
+    #[test]
+    fn feature_set_ids() {
+        assert_eq!("no-features", feature_set_id(&[]));
+
+        let many = (0..100).map(|i| format!("feature-{}", i)).collect::<Vec<_>>();
+        assert_eq!(feature_set_id(&features(&["a"])).len(), feature_set_id(&many).len());
+        assert_eq!(feature_set_id(&features(&["a", "b"])), feature_set_id(&features(&["a", "b"])));
+        assert_ne!(feature_set_id(&features(&["a", "b"])), feature_set_id(&features(&["a"])));
+        assert_ne!(feature_set_id(&features(&["ab"])), feature_set_id(&features(&["a", "b"])));
+    }
+}
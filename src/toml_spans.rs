@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use toml::Spanned;
+
+use crate::feature::Span;
+
+/// The spans of the keys and array elements of a TOML file, which `toml::Value` doesn't keep.
+///
+/// Entries are found by their path from the root table, where array elements are named after their index
+/// (e.g. `["features", "foo", "0"]`). The span of a key covers its value too, unless the value is a table
+/// or an array of tables, whose span is unknown.
+#[derive(Debug, Default)]
+pub(crate) struct TomlSpans {
+    spans: HashMap<Vec<String>, Span>,
+}
+
+impl TomlSpans {
+    /// Finds the spans of a file that was already parsed. If it can't be parsed again, no spans are found.
+    pub(crate) fn new(file_name: &str, contents: &str) -> Self {
+        let mut spans = Self::default();
+        if let Ok(root) = toml::from_str::<Node>(contents) {
+            spans.collect(&root, &mut Vec::new(), &Lines::new(file_name, contents));
+        }
+        spans
+    }
+
+    pub(crate) fn get(&self, path: &[&str]) -> Option<Span> {
+        let path = path.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        self.spans.get(&path).cloned()
+    }
+
+    fn collect(&mut self, node: &Node, path: &mut Vec<String>, lines: &Lines) {
+        let children = match node {
+            Node::Table(entries) => entries
+                .iter()
+                .map(|(key, value)| {
+                    // NOTE: toml marks the spans it can't know (those of tables) as empty ones at the start.
+                    let end = if value.span() == (0, 0) { key.end() } else { value.end() };
+                    (key.get_ref().clone(), Some((key.start(), end)), value.get_ref())
+                })
+                .collect::<Vec<_>>(),
+            Node::Array(elements) => elements
+                .iter()
+                .enumerate()
+                .map(|(index, element)| {
+                    let span = Some(element.span()).filter(|span| *span != (0, 0));
+                    (index.to_string(), span, element.get_ref())
+                })
+                .collect(),
+            Node::Other => return,
+        };
+
+        for (name, span, child) in children {
+            path.push(name);
+            if let Some((start, end)) = span {
+                self.spans.insert(path.clone(), lines.span(start, end));
+            }
+            self.collect(child, path, lines);
+            path.pop();
+        }
+    }
+}
+
+// Turns the byte offsets of a file into spans.
+struct Lines<'a> {
+    file_name: &'a str,
+    contents: &'a str,
+    // The offsets where each line starts.
+    starts: Vec<usize>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(file_name: &'a str, contents: &'a str) -> Self {
+        let starts = Some(0).into_iter().chain(contents.match_indices('\n').map(|(pos, _)| pos + 1)).collect();
+        Self { file_name, contents, starts }
+    }
+
+    // Lines start at 1 and columns at 0, counted in characters like the spans of the compiler.
+    fn position(&self, offset: usize) -> (usize, usize) {
+        let line = match self.starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        (line + 1, self.contents[self.starts[line]..offset].chars().count())
+    }
+
+    fn span(&self, start: usize, end: usize) -> Span {
+        let (start_line, start_col) = self.position(start);
+        let (end_line, end_col) = self.position(end);
+        Span { file_name: self.file_name.into(), start_line, start_col, end_line, end_col }
+    }
+}
+
+// A TOML value that only keeps the structure of the document, along with the spans toml knows about.
+enum Node {
+    Table(Vec<(Spanned<String>, Spanned<Node>)>),
+    Array(Vec<Spanned<Node>>),
+    Other,
+}
+
+impl<'de> Deserialize<'de> for Node {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(NodeVisitor)
+    }
+}
+
+struct NodeVisitor;
+
+impl<'de> Visitor<'de> for NodeVisitor {
+    type Value = Node;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a TOML value")
+    }
+
+    fn visit_bool<E: de::Error>(self, _: bool) -> Result<Node, E> {
+        Ok(Node::Other)
+    }
+
+    fn visit_i64<E: de::Error>(self, _: i64) -> Result<Node, E> {
+        Ok(Node::Other)
+    }
+
+    fn visit_u64<E: de::Error>(self, _: u64) -> Result<Node, E> {
+        Ok(Node::Other)
+    }
+
+    fn visit_f64<E: de::Error>(self, _: f64) -> Result<Node, E> {
+        Ok(Node::Other)
+    }
+
+    fn visit_str<E: de::Error>(self, _: &str) -> Result<Node, E> {
+        Ok(Node::Other)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Node, A::Error> {
+        let mut elements = Vec::new();
+        while let Some(element) = seq.next_element()? {
+            elements.push(element);
+        }
+        Ok(Node::Array(elements))
+    }
+
+    // NOTE: Datetimes are deserialized as maps with an unspanned key, so files with them have no spans.
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Node, A::Error> {
+        let mut entries = Vec::new();
+        while let Some(key) = map.next_key()? {
+            entries.push((key, map.next_value()?));
+        }
+        Ok(Node::Table(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(spans: &TomlSpans, path: &[&str]) -> Option<String> {
+        spans.get(path).map(|span| span.to_string())
+    }
+
+    #[test]
+    fn keys_and_values() {
+        let contents = "[package]\nname = \"foo\"\nmetadata.docs = { all = true }\n";
+        let spans = TomlSpans::new("Cargo.toml", contents);
+
+        assert_eq!(Some("Cargo.toml 2:0 2:12".into()), span(&spans, &["package", "name"]));
+        // Dotted keys and inline tables have spans all the way down.
+        assert_eq!(Some("Cargo.toml 3:9 3:30".into()), span(&spans, &["package", "metadata", "docs"]));
+        assert_eq!(Some("Cargo.toml 3:18 3:28".into()), span(&spans, &["package", "metadata", "docs", "all"]));
+        assert_eq!(None, span(&spans, &["package", "version"]));
+    }
+
+    #[test]
+    fn tables_and_arrays() {
+        let contents = "[features]\ndefault = [\"std\", \"serde\"]\n\n[[bin]]\nname = \"foo\"\n";
+        let spans = TomlSpans::new("Cargo.toml", contents);
+
+        // Only the key of a table header is known.
+        assert_eq!(Some("Cargo.toml 1:1 1:9".into()), span(&spans, &["features"]));
+        assert_eq!(Some("Cargo.toml 2:11 2:16".into()), span(&spans, &["features", "default", "0"]));
+        assert_eq!(Some("Cargo.toml 2:18 2:25".into()), span(&spans, &["features", "default", "1"]));
+        assert_eq!(Some("Cargo.toml 5:0 5:12".into()), span(&spans, &["bin", "0", "name"]));
+        assert_eq!(None, span(&spans, &["bin", "0"]));
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let contents = "description = \"héhé\" \nname = \"foo\"\n";
+        let spans = TomlSpans::new("Cargo.toml", contents);
+        assert_eq!(Some("Cargo.toml 1:0 1:20".into()), span(&spans, &["description"]));
+    }
+
+    #[test]
+    fn no_spans() {
+        assert_eq!(None, span(&TomlSpans::new("Cargo.toml", "[package"), &["package"]));
+        // Datetimes can't be read with their spans, so none of the file's are.
+        let spans = TomlSpans::new("Cargo.toml", "name = \"foo\"\ndate = 1979-05-27\n");
+        assert_eq!(None, span(&spans, &["name"]));
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+
+use cargo_minver::{Analysis, CrateAnalysis, Edition, Span};
+
+fn span(line: usize) -> Span {
+    Span { file_name: "src/main.rs".into(), start_line: line, start_col: 4, end_line: line, end_col: 11 }
+}
+
+fn crate_analysis(target: &str, lines: &[usize]) -> CrateAnalysis {
+    let mut uses = HashMap::new();
+    uses.insert("i128_type".to_string(), lines.iter().map(|&line| span(line)).collect());
+    CrateAnalysis { name: "foo".into(), target: Some(target.into()), uses, ..Default::default() }
+}
+
+#[test]
+fn feature_uses_by_target() {
+    let analysis = Analysis::from(vec![
+        crate_analysis("x86_64-unknown-linux-gnu", &[3, 8]),
+        crate_analysis("wasm32-unknown-unknown", &[3]),
+    ]);
+
+    let uses = analysis.all_feature_uses_by_target("i128_type");
+    let uses = uses.iter().map(ToString::to_string).collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            "src/main.rs 3:4 3:11 [wasm32-unknown-unknown, x86_64-unknown-linux-gnu]",
+            "src/main.rs 8:4 8:11 [x86_64-unknown-linux-gnu]"
+        ],
+        uses,
+        "expected uses to be tagged with their targets"
+    );
+}
+
+#[test]
+fn edition_dependent_features() {
+    let expected = [
+        ("async_await", None, Some("1.39.0")),
+        ("crate_in_paths", Some("1.30.0"), Some("1.31.0")),
+        ("nll", Some("1.36.0"), Some("1.31.0")),
+        ("uniform_paths", None, Some("1.32.0")),
+        ("dyn_trait", None, None),
+    ];
+    for (name, since_2015, since_2018) in &expected {
+        for (edition, since) in &[(Edition::Edition2015, since_2015), (Edition::Edition2018, since_2018)] {
+            let since = since.map(|since| since.parse().unwrap());
+            assert_eq!(since, edition.feature_since(name), "expected `{}` on {} to match", name, edition);
+        }
+    }
+}
@@ -635,5 +635,421 @@ test_lang_features!(
             "15:12 15:19",
             "16:15 16:22"
         ]
+    ),
+    (
+        nll,
+        Edition::Edition2015,
+        "1.36.0",
+        ["5:4 5:10"]
+    ),
+    (
+        two_phase_borrows,
+        Edition::Edition2015,
+        "1.36.0",
+        ["3:4 3:19"]
+    ),
+    (
+        macro_lifetime_matcher,
+        Edition::Edition2015,
+        "1.27.0",
+        ["4:5 4:16"]
+    ),
+    (
+        macro_vis_matcher,
+        Edition::Edition2015,
+        "1.30.0",
+        ["4:5 4:11"]
+    ),
+    (
+        macro_literal_matcher,
+        Edition::Edition2015,
+        "1.32.0",
+        ["4:5 4:15"]
+    ),
+    (
+        macro_at_most_once_rep,
+        Edition::Edition2018,
+        "1.32.0",
+        ["4:18 4:23"]
+    ),
+    (
+        extern_prelude,
+        Edition::Edition2015,
+        "1.30.0",
+        ["5:8 5:11"]
+    ),
+    (
+        uniform_paths,
+        Edition::Edition2018,
+        "1.32.0",
+        ["7:4 7:9"]
+    ),
+    (
+        infer_outlives_requirements,
+        Edition::Edition2015,
+        "1.30.0",
+        ["4:7 4:12"]
+    ),
+    (
+        exhaustive_integer_patterns,
+        Edition::Edition2015,
+        "1.33.0",
+        ["5:4 8:5"]
+    ),
+    (
+        rc_arc_pin_receivers,
+        Edition::Edition2015,
+        "1.33.0",
+        ["10:13 10:27", "11:14 11:34"]
+    ),
+    (
+        nested_receivers,
+        Edition::Edition2015,
+        "1.41.0",
+        ["8:17 8:36"]
+    ),
+    (
+        closure_to_fn_coercion,
+        Edition::Edition2015,
+        "1.19.0",
+        ["4:28 4:37"]
+    ),
+    (
+        re_rebalance_coherence,
+        Edition::Edition2015,
+        "1.41.0",
+        ["5:0 5:30"]
+    ),
+    (
+        const_let,
+        Edition::Edition2015,
+        "1.33.0",
+        ["4:4 4:18"]
+    ),
+    (
+        item_like_imports,
+        Edition::Edition2015,
+        "1.15.0",
+        ["7:12 7:20"]
+    ),
+    (
+        extern_absolute_paths,
+        Edition::Edition2015,
+        "1.30.0",
+        ["3:4 3:8"]
+    ),
+    (
+        use_extern_macros,
+        Edition::Edition2015,
+        "1.30.0",
+        ["3:4 3:16"]
+    ),
+    (
+        copy_closures,
+        Edition::Edition2015,
+        "1.26.0",
+        ["9:8 9:9"]
+    ),
+    (
+        clone_closures,
+        Edition::Edition2015,
+        "1.26.0",
+        ["5:12 5:21"]
     )
 );
+
+fn analyze(project: &util::project::Project) -> anyhow::Result<cargo_minver::Analysis> {
+    cargo_minver::Driver::new()
+        .wrapper_path(util::wrapper_path()?)
+        .manifest_path(project.manifest_path())
+        .quiet(true)
+        .execute()
+}
+
+// Checks that none of the given features are detected in a single file project.
+fn assert_no_features(name: &str, edition: Edition, features: &[&str]) -> anyhow::Result<()> {
+    let project =
+        util::project::Builder::new(name).edition(edition).source_file(format!("lang_files/{}.rs", name))?.create()?;
+
+    let analysis = analyze(&project)?;
+    for feature in features {
+        assert!(analysis.all_feature_uses(feature).is_empty(), "expected no uses of `{}`", feature);
+    }
+    Ok(())
+}
+
+#[test]
+fn inactive_cfg() -> anyhow::Result<()> {
+    let project = util::project::Builder::new("inactive_cfg")
+        .edition(Edition::Edition2015)
+        .source_file("lang_files/inactive_cfg.rs")?
+        .create()?;
+
+    let analysis = cargo_minver::Driver::new()
+        .wrapper_path(util::wrapper_path()?)
+        .manifest_path(project.manifest_path())
+        .quiet(true)
+        .execute()?;
+
+    for (name, span) in &[("conservative_impl_trait", "2:17 2:26"), ("i128_type", "8:12 8:17")] {
+        assert!(analysis.feature(name).is_none(), "expected feature to be inactive");
+
+        let uses = analysis.inactive_feature_uses(name);
+        assert_eq!(1, uses.len(), "expected inactive feature use count to match");
+        assert_eq!(format!("src/main.rs {}", span), format!("{}", uses[0].span), "expected span to match");
+        assert_eq!("any()", uses[0].cfg, "expected cfg predicate to match");
+    }
+    Ok(())
+}
+
+#[test]
+fn edition2018() -> anyhow::Result<()> {
+    let project = util::project::Builder::new("edition2018")
+        .edition(Edition::Edition2018)
+        .source_file("lang_files/edition2018.rs")?
+        .create()?;
+
+    let analysis = cargo_minver::Driver::new()
+        .wrapper_path(util::wrapper_path()?)
+        .manifest_path(project.manifest_path())
+        .quiet(true)
+        .execute()?;
+
+    let feature = analysis.feature("edition2018").expect("feature not found");
+    assert_eq!(Some("1.31.0".parse().unwrap()), feature.since, "expected stabilization version to match");
+    assert_eq!(Some(cargo_minver::Edition::Edition2018), analysis.crate_edition("edition2018"));
+    Ok(())
+}
+
+#[test]
+fn item_like_imports_public() -> anyhow::Result<()> {
+    assert_no_features("item_like_imports_public", Edition::Edition2015, &["item_like_imports"])
+}
+
+#[test]
+fn use_extern_macros_module() -> anyhow::Result<()> {
+    assert_no_features("use_extern_macros_module", Edition::Edition2015, &["use_extern_macros"])
+}
+
+#[test]
+fn uniform_paths_extern() -> anyhow::Result<()> {
+    assert_no_features("uniform_paths_extern", Edition::Edition2018, &["uniform_paths"])
+}
+
+#[test]
+fn plain_receivers() -> anyhow::Result<()> {
+    assert_no_features(
+        "plain_receivers",
+        Edition::Edition2015,
+        &["rc_arc_pin_receivers", "nested_receivers", "arbitrary_self_types"],
+    )
+}
+
+#[test]
+fn non_modrs_mods() -> anyhow::Result<()> {
+    let project = util::project::Builder::new("non_modrs_mods")
+        .edition(Edition::Edition2015)
+        .source_file("lang_files/non_modrs_mods/main.rs")?
+        .source_file_at("lang_files/non_modrs_mods/foo.rs", "foo.rs")?
+        .source_file_at("lang_files/non_modrs_mods/foo/bar.rs", "foo/bar.rs")?
+        .create()?;
+
+    let analysis = analyze(&project)?;
+    let feature = analysis.feature("non_modrs_mods").expect("feature not found");
+    assert_eq!(Some("1.30.0".parse().unwrap()), feature.since, "expected stabilization version to match");
+
+    let uses = analysis.all_feature_uses("non_modrs_mods");
+    assert_eq!(1, uses.len(), "expected feature use count to match");
+    assert_eq!("src/foo.rs 1:0 1:8", format!("{}", uses[0]), "expected span to match");
+    Ok(())
+}
+
+#[test]
+fn rustversion_since() -> anyhow::Result<()> {
+    let project = util::project::Builder::new("rustversion_since")
+        .edition(Edition::Edition2018)
+        .source_file("lang_files/rustversion_since.rs")?
+        .dependency("rustversion", "1")
+        .create()?;
+
+    let analysis = analyze(&project)?;
+    assert!(analysis.feature("dyn_trait").is_none(), "expected feature to be conditional");
+
+    let uses = analysis.conditional_feature_uses("dyn_trait");
+    assert_eq!(1, uses.len(), "expected conditional feature use count to match");
+    assert_eq!("src/main.rs 6:11 6:16", format!("{}", uses[0].span), "expected span to match");
+    assert_eq!("rustversion::since(1.27)", uses[0].gate.condition, "expected gate condition to match");
+    assert_eq!("1.27.0".parse::<semver::Version>().unwrap(), uses[0].gate.version, "expected gate version to match");
+    Ok(())
+}
+
+// Gates without a lower bound don't keep the code from the oldest compilers.
+#[test]
+fn rustversion_before() -> anyhow::Result<()> {
+    let project = util::project::Builder::new("rustversion_before")
+        .edition(Edition::Edition2018)
+        .source_file("lang_files/rustversion_before.rs")?
+        .dependency("rustversion", "1")
+        .create()?;
+
+    let analysis = analyze(&project)?;
+    assert!(analysis.feature("dyn_trait").is_some(), "expected feature to be unconditional");
+    assert!(analysis.conditional_feature_uses("dyn_trait").is_empty(), "expected no conditional feature uses");
+
+    let uses = analysis.all_feature_uses("dyn_trait");
+    assert_eq!(1, uses.len(), "expected feature use count to match");
+    assert_eq!("src/main.rs 6:11 6:16", format!("{}", uses[0]), "expected span to match");
+    Ok(())
+}
+
+#[test]
+fn coverage_floor() -> anyhow::Result<()> {
+    let report = cargo_minver::Driver::new().wrapper_path(util::wrapper_path()?).coverage()?;
+
+    let floor = report.blind_spots_since.expect("expected blind spots");
+    assert!(floor > "1.0.0".parse().unwrap(), "expected features of 1.0.0 to be left out of the blind spots");
+    Ok(())
+}
+
+// Features that are still unstable in the pinned compiler are reported without a stabilization version.
+#[test]
+fn const_context_nightly() -> anyhow::Result<()> {
+    let project = util::project::Builder::new("const_context_nightly")
+        .edition(Edition::Edition2015)
+        .source_file("lang_files/const_context_nightly.rs")?
+        .create()?;
+
+    let analysis = analyze(&project)?;
+    for (name, span) in &[("const_fn", "4:17 4:18"), ("const_if_match", "9:4 13:5"), ("const_loop", "18:4 20:5")] {
+        let feature = analysis.feature(name).expect("feature not found");
+        assert_eq!(cargo_minver::FeatureKind::Lang, feature.kind, "expected feature kind to match");
+        assert_eq!(None, feature.since, "expected feature to be nightly-only");
+
+        let uses = analysis.all_feature_uses(name);
+        assert_eq!(1, uses.len(), "expected feature use count to match");
+        assert_eq!(format!("src/main.rs {}", span), format!("{}", uses[0]), "expected span to match");
+    }
+    Ok(())
+}
+
+// In fast mode, only the syntactic passes run and the features the other passes detect are listed as unchecked.
+#[test]
+fn fast_mode() -> anyhow::Result<()> {
+    let project = util::project::Builder::new("fast_mode")
+        .edition(Edition::Edition2018)
+        .source_file("lang_files/fast_mode/main.rs")?
+        .source_file_at("lang_files/fast_mode/lib.rs", "lib.rs")?
+        .create()?;
+
+    let analysis = cargo_minver::Driver::new()
+        .wrapper_path(util::wrapper_path()?)
+        .manifest_path(project.manifest_path())
+        .fast(true)
+        .quiet(true)
+        .execute()?;
+
+    // Features found before the analysis are still reported, in the library and the binary alike.
+    for feature in &["braced_empty_structs", "field_init_shorthand", "i128_type", "relaxed_adts"] {
+        assert!(!analysis.all_feature_uses(feature).is_empty(), "expected uses of `{}`", feature);
+    }
+
+    // Features that are also detected by the syntactic passes are not reported as unchecked.
+    let unchecked = analysis.unchecked_features().into_iter().map(|f| f.name.as_str()).collect::<Vec<_>>();
+    let expected = [
+        "arbitrary_self_types",
+        "augmented_assignments",
+        "bind_by_move_pattern_guards",
+        "clone_closures",
+        "closure_to_fn_coercion",
+        "const_constructor",
+        "const_fn",
+        "const_if_match",
+        "const_let",
+        "const_loop",
+        "copy_closures",
+        "exhaustive_integer_patterns",
+        "impl_header_lifetime_elision",
+        "infer_outlives_requirements",
+        "irrefutable_let_patterns",
+        "match_default_bindings",
+        "min_const_unsafe_fn",
+        "more_struct_aliases",
+        "nll",
+        "pub_restricted",
+        "re_rebalance_coherence",
+        "self_in_typedefs",
+        "self_struct_ctor",
+        "termination_trait",
+        "two_phase_borrows",
+        "type_alias_enum_variants",
+    ];
+    assert_eq!(&expected[..], &unchecked[..], "expected unchecked features to match");
+    Ok(())
+}
+
+#[test]
+fn proc_macro_defs() -> anyhow::Result<()> {
+    let project = util::project::Builder::new("proc_macro_defs")
+        .edition(Edition::Edition2015)
+        .source_file("lang_files/proc_macro_defs.rs")?
+        .proc_macro(true)
+        .create()?;
+
+    let analysis = analyze(&project)?;
+    let expected = [
+        ("proc_macro_derive", "1.15.0", "5:0 5:25"),
+        ("proc_macro_attribute", "1.29.0", "10:0 10:23"),
+        ("proc_macro", "1.29.0", "15:0 15:13"),
+    ];
+    for (name, version, span) in &expected {
+        let feature = analysis.feature(name).expect("feature not found");
+        assert_eq!(Some(version.parse().unwrap()), feature.since, "expected stabilization version to match");
+
+        let uses = analysis.all_feature_uses(name);
+        assert_eq!(1, uses.len(), "expected feature use count to match");
+        assert_eq!(format!("src/lib.rs {}", span), format!("{}", uses[0]), "expected span to match");
+    }
+    Ok(())
+}
+
+// Invocations are found through the items they expand to, or pass through, in the downstream crate.
+#[test]
+fn proc_macro_uses() -> anyhow::Result<()> {
+    let proc_macro_project = util::project::Builder::new("proc_macro_defs")
+        .edition(Edition::Edition2015)
+        .source_file("lang_files/proc_macro_defs.rs")?
+        .proc_macro(true)
+        .create()?;
+    let project = util::project::Builder::new("proc_macro_uses")
+        .edition(Edition::Edition2018)
+        .source_file("lang_files/proc_macro_uses.rs")?
+        .path_dependency("proc_macro_defs", &proc_macro_project)
+        .create()?;
+
+    let analysis = analyze(&project)?;
+    // The derive and the attribute are imported into a submodule, the function-like macro is invoked by path.
+    let expected =
+        [("proc_macro_derive", 4), ("proc_macro_attribute", 7), ("proc_macro", 11), ("proc_macro_path_invoc", 11)];
+    for (name, line) in &expected {
+        let uses = analysis.all_feature_uses(name);
+        let uses = uses.into_iter().filter(|span| span.file_name.ends_with("main.rs")).collect::<Vec<_>>();
+        assert_eq!(1, uses.len(), "expected `{}` use count to match", name);
+        assert_eq!(*line, uses[0].start_line, "expected `{}` use line to match", name);
+    }
+    Ok(())
+}
+
+// `crate::` paths were accepted on 2015 before the 2018 edition was stabilized.
+#[test]
+fn crate_in_paths_2018() -> anyhow::Result<()> {
+    let project = util::project::Builder::new("crate_in_paths_2018")
+        .edition(Edition::Edition2018)
+        .source_file("lang_files/crate_in_paths.rs")?
+        .create()?;
+
+    let analysis = analyze(&project)?;
+    let feature = analysis.feature("crate_in_paths").expect("feature not found");
+    assert_eq!(Some("1.31.0".parse().unwrap()), feature.since, "expected stabilization version to match");
+    assert_eq!(Some(cargo_minver::Edition::Edition2018), analysis.crate_edition("crate_in_paths_2018"));
+    Ok(())
+}
@@ -0,0 +1,7 @@
+#![allow(unused)]
+
+fn main() {
+    let f = || {};
+    let g = f.clone();
+    let h = (&&f).clone();
+}
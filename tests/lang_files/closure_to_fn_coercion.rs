@@ -0,0 +1,5 @@
+#![allow(unused)]
+
+fn main() {
+    let f: fn(u32) -> u32 = |x| x + 1;
+}
@@ -0,0 +1,24 @@
+#![feature(const_fn, const_if_match, const_loop)]
+#![allow(unused)]
+
+const fn bounded<T: Copy>(t: T) -> T {
+    t
+}
+
+const fn pick(b: bool) -> u32 {
+    if b {
+        1
+    } else {
+        0
+    }
+}
+
+const fn count(n: u32) -> u32 {
+    let mut i = 0;
+    while i < n {
+        i += 1;
+    }
+    i
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+#![allow(unused)]
+
+const fn square(x: u32) -> u32 {
+    let y = x * x;
+    y
+}
+
+fn main() {}
@@ -0,0 +1,18 @@
+#![allow(unused)]
+
+fn run<F: Fn()>(f: F) {
+    f();
+}
+
+fn main() {
+    let f = || {};
+    run(f);
+    run(f);
+
+    let g = || {};
+    if true {
+        run(g);
+    } else {
+        run(g);
+    }
+}
@@ -0,0 +1,14 @@
+#![allow(unused)]
+
+fn main() {
+    let x = 0u8;
+    match x {
+        0..=127 => {},
+        128..=255 => {},
+    }
+
+    match x {
+        0 => {},
+        _ => {},
+    }
+}
@@ -0,0 +1,5 @@
+#![allow(unused)]
+
+use core::mem;
+
+fn main() {}
@@ -0,0 +1,9 @@
+#![allow(unused)]
+
+mod inner {
+    pub fn f() {
+        std::mem::drop(0);
+    }
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+pub struct Empty {}
+
+pub struct Point {
+    pub x: i32,
+}
+
+pub fn point(x: i32) -> Point {
+    Point { x }
+}
+
+pub fn wide() -> i128 {
+    1i128
+}
@@ -0,0 +1,5 @@
+struct Unit();
+
+fn main() {
+    let _ = Unit();
+}
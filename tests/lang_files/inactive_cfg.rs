@@ -0,0 +1,9 @@
+#[cfg(any())]
+fn disabled() -> impl Fn() {
+    || {}
+}
+
+fn main() {
+    #[cfg(any())]
+    let _ = 1u128;
+}
@@ -0,0 +1,11 @@
+#![allow(unused)]
+
+struct Ref<'a, T> {
+    x: &'a T,
+}
+
+struct Explicit<'a, T: 'a> {
+    x: &'a T,
+}
+
+fn main() {}
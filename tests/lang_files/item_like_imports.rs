@@ -0,0 +1,15 @@
+#![allow(unused)]
+
+mod inner {
+    fn helper() {}
+
+    mod tests {
+        use super::*;
+
+        fn test() {
+            helper();
+        }
+    }
+}
+
+fn main() {}
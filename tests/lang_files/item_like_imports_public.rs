@@ -0,0 +1,19 @@
+#![allow(unused)]
+
+mod inner {
+    pub struct Foo;
+
+    impl Foo {}
+
+    extern "C" {}
+
+    mod tests {
+        use super::*;
+
+        fn test() {
+            let _ = Foo;
+        }
+    }
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+#![allow(unused)]
+
+macro_rules! optional {
+    ($($x:expr),* $(,)?) => {
+        [$($x),*]
+    };
+}
+
+fn main() {
+    let _ = optional!(1, 2,);
+}
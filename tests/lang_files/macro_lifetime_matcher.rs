@@ -0,0 +1,11 @@
+#![allow(unused)]
+
+macro_rules! lifetime {
+    ($l:lifetime) => {
+        fn f<$l>(x: &$l str) -> &$l str { x }
+    };
+}
+
+lifetime!('a);
+
+fn main() {}
@@ -0,0 +1,11 @@
+#![allow(unused)]
+
+macro_rules! literal {
+    ($l:literal) => {
+        $l
+    };
+}
+
+fn main() {
+    let _ = literal!(1);
+}
@@ -0,0 +1,11 @@
+#![allow(unused)]
+
+macro_rules! item {
+    ($v:vis $name:ident) => {
+        $v struct $name;
+    };
+}
+
+item!(pub Foo);
+
+fn main() {}
@@ -0,0 +1,11 @@
+#![allow(unused)]
+
+use std::rc::Rc;
+
+struct Foo;
+
+impl Foo {
+    fn by_rc_box(self: Rc<Box<Self>>) {}
+}
+
+fn main() {}
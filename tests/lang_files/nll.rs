@@ -0,0 +1,6 @@
+fn main() {
+    let mut x = 1;
+    let r = &mut x;
+    *r += 1;
+    x += 1;
+}
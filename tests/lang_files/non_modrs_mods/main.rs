@@ -0,0 +1,5 @@
+#![allow(unused)]
+
+mod foo;
+
+fn main() {}
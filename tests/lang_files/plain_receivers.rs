@@ -0,0 +1,18 @@
+#![allow(unused)]
+
+struct Foo;
+
+impl Foo {
+    fn by_value(self) {}
+    fn by_ref(&self) {}
+    fn by_mut(&mut self) {}
+    fn by_explicit_ref(self: &Self) {}
+    fn by_box(self: Box<Self>) {}
+}
+
+trait Bar {
+    fn by_ref(&self) {}
+    fn by_box(self: Box<Self>);
+}
+
+fn main() {}
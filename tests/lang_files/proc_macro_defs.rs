@@ -0,0 +1,18 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+
+#[proc_macro_derive(Foo)]
+pub fn derive_foo(_: TokenStream) -> TokenStream {
+    "fn derived() {}".parse().unwrap()
+}
+
+#[proc_macro_attribute]
+pub fn foo_attr(_: TokenStream, item: TokenStream) -> TokenStream {
+    item
+}
+
+#[proc_macro]
+pub fn foo(input: TokenStream) -> TokenStream {
+    input
+}
@@ -0,0 +1,15 @@
+mod inner {
+    use proc_macro_defs::{foo_attr, Foo};
+
+    #[derive(Foo)]
+    pub struct Derived;
+
+    #[foo_attr]
+    pub fn attributed() {}
+}
+
+proc_macro_defs::foo!(
+    pub struct Invoked;
+);
+
+fn main() {}
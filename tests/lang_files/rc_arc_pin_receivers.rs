@@ -0,0 +1,14 @@
+#![allow(unused)]
+
+use std::pin::Pin;
+use std::rc::Rc;
+
+struct Foo;
+
+impl Foo {
+    fn by_ref(&self) {}
+    fn by_rc(self: Rc<Self>) {}
+    fn by_pin(self: Pin<&mut Self>) {}
+}
+
+fn main() {}
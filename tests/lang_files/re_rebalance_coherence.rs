@@ -0,0 +1,17 @@
+#![allow(unused)]
+
+struct Local;
+
+impl<T> From<Local> for Vec<T> {
+    fn from(_: Local) -> Self {
+        Vec::new()
+    }
+}
+
+impl From<Local> for String {
+    fn from(_: Local) -> Self {
+        String::new()
+    }
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+#![allow(unused)]
+
+trait T {}
+
+#[rustversion::before(1.40)]
+fn fun(t: &dyn T) {}
+
+fn main() {}
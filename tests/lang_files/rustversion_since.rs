@@ -0,0 +1,8 @@
+#![allow(unused)]
+
+trait T {}
+
+#[rustversion::since(1.27)]
+fn fun(t: &dyn T) {}
+
+fn main() {}
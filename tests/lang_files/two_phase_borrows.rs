@@ -0,0 +1,4 @@
+fn main() {
+    let mut v = vec![1];
+    v.push(v.len());
+}
@@ -0,0 +1,9 @@
+#![allow(unused)]
+
+mod inner {
+    pub fn f() {}
+}
+
+use inner::f;
+
+fn main() {}
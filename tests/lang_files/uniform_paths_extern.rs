@@ -0,0 +1,5 @@
+#![allow(unused)]
+
+use std::mem;
+
+fn main() {}
@@ -0,0 +1,5 @@
+#![allow(unused)]
+
+use std::println;
+
+fn main() {}
@@ -0,0 +1,9 @@
+#![allow(unused)]
+
+use std::env;
+use std::vec;
+
+fn main() {
+    let _ = env::args();
+    let _ = vec![0];
+}
@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::{env, fmt, fs};
 
 use anyhow::{format_err, Result};
@@ -26,7 +26,12 @@ pub enum PanicBehavior {
 pub struct Builder {
     name: String,
     edition: Edition,
-    source_files: Vec<PathBuf>,
+    // Source files along with their path in the `src` directory, if it's not the default one.
+    source_files: Vec<(PathBuf, Option<PathBuf>)>,
+    dependencies: Vec<(String, String)>,
+    path_dependencies: Vec<(String, PathBuf)>,
+    // Proc macro crates are libraries, so the first source file becomes `lib.rs`.
+    proc_macro: bool,
     on_panic: PanicBehavior,
 }
 
@@ -36,6 +41,9 @@ impl Builder {
             name: name.into(),
             edition: Edition::Edition2015,
             source_files: Vec::new(),
+            dependencies: Vec::new(),
+            path_dependencies: Vec::new(),
+            proc_macro: false,
             on_panic: PanicBehavior::Unwind,
         }
     }
@@ -50,10 +58,33 @@ impl Builder {
         target.push("tests");
         target.push(path.into());
 
-        self.source_files.push(target);
+        self.source_files.push((target, None));
         Ok(self)
     }
 
+    // Adds a source file at the given path relative to the `src` directory, e.g. for modules in subdirectories.
+    pub fn source_file_at<P: Into<PathBuf>, Q: Into<PathBuf>>(&mut self, path: P, dest: Q) -> Result<&mut Self> {
+        self.source_file(path)?;
+        self.source_files.last_mut().unwrap().1 = Some(dest.into());
+        Ok(self)
+    }
+
+    pub fn dependency(&mut self, name: &str, version: &str) -> &mut Self {
+        self.dependencies.push((name.into(), version.into()));
+        self
+    }
+
+    // Depends on another project, e.g. a proc macro crate.
+    pub fn path_dependency(&mut self, name: &str, project: &Project) -> &mut Self {
+        self.path_dependencies.push((name.into(), project.dir().into()));
+        self
+    }
+
+    pub fn proc_macro(&mut self, proc_macro: bool) -> &mut Self {
+        self.proc_macro = proc_macro;
+        self
+    }
+
     pub fn on_panic(&mut self, on_panic: PanicBehavior) -> &mut Self {
         self.on_panic = on_panic;
         self
@@ -68,11 +99,15 @@ impl Builder {
         let src_dir = project_dir.path().join("src");
         fs::create_dir(&src_dir)?;
 
-        for (num, source_file) in self.source_files.iter().enumerate() {
-            let file_name: PathBuf = if num == 0 { "main.rs".into() } else { source_file.file_name().unwrap().into() };
+        for (num, (source_file, dest)) in self.source_files.iter().enumerate() {
+            let file_name: PathBuf = match dest {
+                Some(dest) => dest.clone(),
+                None if num == 0 => if self.proc_macro { "lib.rs" } else { "main.rs" }.into(),
+                None => source_file.file_name().unwrap().into(),
+            };
 
-            let mut dest = src_dir.clone();
-            dest.push(file_name);
+            let dest = src_dir.join(file_name);
+            fs::create_dir_all(dest.parent().unwrap())?;
             fs::copy(source_file, dest)?;
         }
 
@@ -86,6 +121,24 @@ edition = "{}"
             self.name, self.edition
         );
 
+        if self.proc_macro {
+            manifest.push_str(
+                r#"[lib]
+proc-macro = true
+"#,
+            );
+        }
+
+        if !self.dependencies.is_empty() || !self.path_dependencies.is_empty() {
+            manifest.push_str("[dependencies]\n");
+            for (name, version) in &self.dependencies {
+                manifest.push_str(&format!("{} = \"{}\"\n", name, version));
+            }
+            for (name, path) in &self.path_dependencies {
+                manifest.push_str(&format!("{} = {{ path = {:?} }}\n", name, path));
+            }
+        }
+
         if let PanicBehavior::Abort = self.on_panic {
             manifest.push_str(
                 r#"[profile.dev]
@@ -109,6 +162,10 @@ impl Project {
         &self._name
     }
 
+    pub fn dir(&self) -> &Path {
+        self.dir.path()
+    }
+
     pub fn manifest_path(&self) -> PathBuf {
         self.dir.path().join("Cargo.toml")
     }